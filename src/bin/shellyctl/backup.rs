@@ -0,0 +1,337 @@
+use crate::cli::{BackupArgs, RestoreArgs};
+use crate::config::apply::{apply_changes, plan, print_plan};
+use crate::config::dump::fetch_config;
+use crate::config::set::list_methods;
+use crate::config::tree::is_ignored;
+use crate::rpc::call;
+use crate::script::download::fetch_code;
+use crate::script::upload::upload;
+use anyhow::{anyhow, bail, Result};
+use log::{debug, info};
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Config keys that belong to a particular unit and are never restored.
+///
+/// Wi-Fi credentials are not part of Shelly.GetConfig, and script components
+/// are recreated from the archived scripts instead.
+const IDENTITY_KEYS: &[&str] = &["wifi.sta", "wifi.sta1", "wifi.ap", "script:*"];
+
+pub async fn backup(args: BackupArgs) -> Result<()> {
+    let client = Client::new();
+    let device = args.device.as_str();
+
+    let methods = list_methods(&client, device).await?;
+    let has = |method: &str| methods.iter().any(|m| m.eq_ignore_ascii_case(method));
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+
+    let info = call(&client, device, "Shelly.GetDeviceInfo", json!({})).await?;
+    files.push(("device.json".into(), serde_json::to_vec_pretty(&info)?));
+
+    let config = fetch_config(&client, device).await?;
+    files.push(("config.json".into(), serde_json::to_vec_pretty(&config)?));
+
+    let mut scripts = Vec::new();
+    if has("Script.List") {
+        let list = call(&client, device, "Script.List", json!({})).await?;
+        for script in list["scripts"].as_array().into_iter().flatten() {
+            let id = script["id"]
+                .as_u64()
+                .ok_or_else(|| anyhow!("Missing or invalid script ID"))?;
+            let code = fetch_code(&client, device, id).await?;
+            files.push((format!("scripts/{}.js", id), code.into_bytes()));
+            scripts.push(json!({
+                "id": id,
+                "name": script["name"],
+                "enable": script["enable"],
+            }));
+        }
+    }
+    files.push(("scripts.json".into(), serde_json::to_vec_pretty(&scripts)?));
+
+    let kvs = if has("KVS.GetMany") {
+        fetch_kvs(&client, device).await?
+    } else {
+        Map::new()
+    };
+    files.push(("kvs.json".into(), serde_json::to_vec_pretty(&kvs)?));
+
+    let schedules = if has("Schedule.List") {
+        call(&client, device, "Schedule.List", json!({})).await?["jobs"].take()
+    } else {
+        json!([])
+    };
+    files.push((
+        "schedules.json".into(),
+        serde_json::to_vec_pretty(&schedules)?,
+    ));
+
+    let webhooks = if has("Webhook.List") {
+        call(&client, device, "Webhook.List", json!({})).await?["hooks"].take()
+    } else {
+        json!([])
+    };
+    files.push((
+        "webhooks.json".into(),
+        serde_json::to_vec_pretty(&webhooks)?,
+    ));
+
+    write_archive(&args.output, &files)?;
+
+    println!(
+        "✅ Saved config, {} script(s), {} KVS entr(y/ies), {} schedule(s) and {} webhook(s) of {} to '{}'",
+        scripts.len(),
+        kvs.len(),
+        schedules.as_array().map_or(0, Vec::len),
+        webhooks.as_array().map_or(0, Vec::len),
+        device,
+        args.output
+    );
+    Ok(())
+}
+
+pub async fn restore(args: RestoreArgs) -> Result<()> {
+    let client = Client::new();
+    let device = args.device.as_str();
+
+    let files = read_archive(&args.input)?;
+    let json_file = |name: &str| -> Result<Value> {
+        let data = files
+            .get(name)
+            .ok_or_else(|| anyhow!("'{}' is missing {}", args.input, name))?;
+        serde_json::from_slice(data).map_err(|e| anyhow!("{}: {}", name, e))
+    };
+
+    let old_info = json_file("device.json")?;
+    let new_info = call(&client, device, "Shelly.GetDeviceInfo", json!({})).await?;
+
+    let methods = list_methods(&client, device).await?;
+    let has = |method: &str| methods.iter().any(|m| m.eq_ignore_ascii_case(method));
+
+    // 1. Config, with references to the old device id pointed at the new one
+    let mut config = json_file("config.json")?;
+    if !config.is_object() {
+        bail!("config.json: expected an object of components");
+    }
+    if let (Some(old_id), Some(new_id)) = (old_info["id"].as_str(), new_info["id"].as_str()) {
+        replace_strings(&mut config, old_id, new_id);
+    }
+
+    let current = fetch_config(&client, device).await?;
+    let mut missing = BTreeSet::new();
+    let changes: Vec<_> = plan(&current, &config)
+        .into_iter()
+        .filter(|d| !is_ignored(&d.key(), IDENTITY_KEYS))
+        .filter(|d| {
+            let component = &d.path[0];
+            let present = current.get(component).is_some();
+            if !present {
+                missing.insert(component.clone());
+            }
+            present
+        })
+        .collect();
+
+    for component in &missing {
+        eprintln!("⚠️  Skipping {}: not present on {}", component, device);
+    }
+    if changes.is_empty() {
+        println!("✅ Config already matches the backup");
+    } else {
+        print_plan(device, &changes);
+        apply_changes(&client, device, &changes).await?;
+    }
+
+    // 2. KVS
+    let kvs = json_file("kvs.json")?;
+    for (key, value) in kvs.as_object().into_iter().flatten() {
+        call(
+            &client,
+            device,
+            "KVS.Set",
+            json!({ "key": key, "value": value }),
+        )
+        .await?;
+    }
+    println!(
+        "✅ Restored {} KVS entr(y/ies)",
+        kvs.as_object().map_or(0, Map::len)
+    );
+
+    // 3. Scripts, remembering their new ids for schedules and webhooks
+    let mut script_ids = HashMap::<u64, u64>::new();
+    for script in json_file("scripts.json")?.as_array().into_iter().flatten() {
+        let old_id = script["id"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Missing or invalid script ID"))?;
+        let name = script["name"].as_str().unwrap_or_default();
+        let code_file = format!("scripts/{}.js", old_id);
+        let code = files
+            .get(&code_file)
+            .ok_or_else(|| anyhow!("'{}' is missing {}", args.input, code_file))?;
+        let code = String::from_utf8_lossy(code);
+
+        let new_id = upload(&client, device, name, &code, true, false)
+            .await?
+            .ok_or_else(|| anyhow!("Script '{}' could not be uploaded", name))?
+            as u64;
+        script_ids.insert(old_id, new_id);
+
+        if script["enable"].as_bool().unwrap_or(false) {
+            call(
+                &client,
+                device,
+                "Script.SetConfig",
+                json!({ "id": new_id, "config": { "enable": true } }),
+            )
+            .await?;
+            call(&client, device, "Script.Start", json!({ "id": new_id })).await?;
+        }
+        println!("✅ Restored script '{}' (ID {} → {})", name, old_id, new_id);
+    }
+
+    // 4. Schedules
+    // Replace rather than add to existing jobs, so restoring twice doesn't duplicate them
+    let schedules = json_file("schedules.json")?;
+    if has("Schedule.DeleteAll") {
+        call(&client, device, "Schedule.DeleteAll", json!({})).await?;
+    }
+    for job in schedules.as_array().into_iter().flatten() {
+        let mut job = job.clone();
+        if let Some(job) = job.as_object_mut() {
+            job.remove("id");
+        }
+        let calls = job.get_mut("calls").and_then(Value::as_array_mut);
+        for rpc in calls.into_iter().flatten() {
+            let is_script = rpc["method"]
+                .as_str()
+                .is_some_and(|m| m.to_ascii_lowercase().starts_with("script."));
+            if let Some(id) = rpc.pointer_mut("/params/id").filter(|_| is_script) {
+                remap_id(id, &script_ids);
+            }
+        }
+        let res = call(&client, device, "Schedule.Create", job).await?;
+        debug!("Schedule.Create response: {:?}", res);
+    }
+    println!(
+        "✅ Restored {} schedule(s)",
+        schedules.as_array().map_or(0, Vec::len)
+    );
+
+    // 5. Webhooks
+    let webhooks = json_file("webhooks.json")?;
+    if has("Webhook.DeleteAll") {
+        call(&client, device, "Webhook.DeleteAll", json!({})).await?;
+    }
+    for hook in webhooks.as_array().into_iter().flatten() {
+        let mut hook = hook.clone();
+        if let Some(hook) = hook.as_object_mut() {
+            hook.remove("id");
+        }
+        let is_script = hook["event"]
+            .as_str()
+            .is_some_and(|e| e.starts_with("script."));
+        if let Some(cid) = hook.get_mut("cid").filter(|_| is_script) {
+            remap_id(cid, &script_ids);
+        }
+        let res = call(&client, device, "Webhook.Create", hook).await?;
+        debug!("Webhook.Create response: {:?}", res);
+    }
+    println!(
+        "✅ Restored {} webhook(s)",
+        webhooks.as_array().map_or(0, Vec::len)
+    );
+
+    Ok(())
+}
+
+/// Reads all KVS entries, following KVS.GetMany pagination.
+async fn fetch_kvs(client: &Client, device: &str) -> Result<Map<String, Value>> {
+    let mut items = Map::new();
+    loop {
+        let resp = call(
+            client,
+            device,
+            "KVS.GetMany",
+            json!({ "offset": items.len() }),
+        )
+        .await?;
+        let before = items.len();
+
+        // Older firmware returns an object keyed by name, newer an array of entries
+        match &resp["items"] {
+            Value::Object(map) => {
+                for (key, item) in map {
+                    items.insert(key.clone(), item["value"].clone());
+                }
+            }
+            Value::Array(arr) => {
+                for item in arr {
+                    if let Some(key) = item["key"].as_str() {
+                        items.insert(key.to_string(), item["value"].clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let total = resp["total"].as_u64().unwrap_or(0) as usize;
+        if items.len() >= total || items.len() == before {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+fn remap_id(id: &mut Value, ids: &HashMap<u64, u64>) {
+    if let Some(new_id) = id.as_u64().and_then(|old| ids.get(&old)) {
+        *id = json!(new_id);
+    }
+}
+
+fn replace_strings(value: &mut Value, from: &str, to: &str) {
+    match value {
+        Value::String(s) if s.contains(from) => *s = s.replace(from, to),
+        Value::Object(map) => map.values_mut().for_each(|v| replace_strings(v, from, to)),
+        Value::Array(arr) => arr.iter_mut().for_each(|v| replace_strings(v, from, to)),
+        _ => {}
+    }
+}
+
+fn write_archive(path: &str, files: &[(String, Vec<u8>)]) -> Result<()> {
+    let file = File::create(path).map_err(|e| anyhow!("Failed to create {}: {}", path, e))?;
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let mut builder = tar::Builder::new(file);
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+    builder.finish()?;
+    info!("Wrote {} files to {}", files.len(), path);
+    Ok(())
+}
+
+fn read_archive(path: &str) -> Result<HashMap<String, Vec<u8>>> {
+    let file = File::open(path).map_err(|e| anyhow!("Failed to open {}: {}", path, e))?;
+    let mut archive = tar::Archive::new(file);
+
+    let mut files = HashMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        files.insert(name, data);
+    }
+    Ok(files)
+}
@@ -1,13 +1,22 @@
-use crate::cli::BrowseArgs;
+use crate::cli::{BrowseArgs, BrowseColumn, BrowseOutput, BrowseSort};
+use crate::resolve::{self, Cached};
+use crate::rpc::rpc_url;
+use crate::table::plain_table;
 use std::collections::{BTreeMap, HashSet};
-use std::io::{stdout, Write};
+use std::fmt;
+use std::io::{stdout, IsTerminal, Write};
 use std::net::IpAddr;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
-use mdns_sd::{ServiceDaemon, ServiceEvent};
+use log::debug;
+use mdns_sd::{IfKind, ServiceDaemon, ServiceEvent, ServiceInfo};
 use reqwest::Client;
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tokio::sync::{mpsc, Semaphore};
 use tokio::time::sleep;
 
 use crossterm::{
@@ -15,209 +24,636 @@ use crossterm::{
     execute,
     terminal::{Clear, ClearType},
 };
-use prettytable::{
-    format::{FormatBuilder, LinePosition, LineSeparator},
-    Cell, Row, Table,
-};
+use prettytable::{Cell, Row, Table};
+
+mod filter;
+mod scan;
+mod tui;
+mod watch;
+
+pub use filter::Filter;
+
+/// Probes allowed in flight at once while browsing.
+const MAX_PROBES: usize = 16;
+
+/// Per-request timeout of a device probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Advertised by Gen2+ devices, with `gen`, `app` and `ver` TXT records.
+const SHELLY_SERVICE: &str = "_shelly._tcp.local.";
+
+/// Advertised by every web server, Gen1 devices included.
+const HTTP_SERVICE: &str = "_http._tcp.local.";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShellyDevice {
+    pub hostname: String,
+    pub ip: String,
+    /// Every address the device advertises, `ip` first.
+    pub addresses: Vec<IpAddr>,
+    pub id: String,
+    pub mac: String,
+    pub model: String,
+    pub gen: u32,
+    pub app: String,
+    pub profile: String,
+    pub ver: String,
+    pub name: String,
+    pub ssid: String,
+    pub rssi: i32,
+    pub uptime: Option<u64>,
+    /// Highest internal temperature any component reports, in °C.
+    pub temperature: Option<f64>,
+    /// Firmware version on offer, stable preferred over beta.
+    pub update: Option<String>,
+    pub cloud: Option<bool>,
+    pub mqtt: Option<bool>,
+    pub restart_required: Option<bool>,
+}
+
+impl ShellyDevice {
+    /// A device at `ip` that nothing is known about yet.
+    fn new(hostname: String, ip: IpAddr) -> Self {
+        let unknown = || "-".to_string();
+        ShellyDevice {
+            hostname,
+            ip: ip.to_string(),
+            addresses: vec![ip],
+            id: unknown(),
+            mac: unknown(),
+            model: unknown(),
+            gen: 0,
+            app: unknown(),
+            profile: unknown(),
+            ver: unknown(),
+            name: unknown(),
+            ssid: unknown(),
+            rssi: 0,
+            uptime: None,
+            temperature: None,
+            update: None,
+            cloud: None,
+            mqtt: None,
+            restart_required: None,
+        }
+    }
+}
+
+/// Fields of a serialized device in declaration order, one CSV column each.
+struct Fields(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Fields, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    fields.push(entry);
+                }
+                Ok(Fields(fields))
+            }
+        }
 
-#[derive(Debug, Clone)]
-struct ShellyDevice {
-    ip: String,
-    gen: u32,
-    name: String,
-    ver: String,
-    app: String,
-    profile: String,
-    hostname: String,
-    ssid: String,
-    rssi: i32,
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+/// One CSV cell; lists such as `addresses` are joined with spaces.
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(csv_cell).collect::<Vec<_>>().join(" "),
+        other => other.to_string(),
+    }
 }
 
 pub async fn handle(args: BrowseArgs) -> Result<()> {
-    let mdns = ServiceDaemon::new()?;
-    let service_type = "_http._tcp.local.";
-    let receiver = mdns.browse(service_type)?;
+    let filter = Filter::new(&args)?;
+    let search = Search {
+        scan: args.scan.as_deref(),
+        interface: args.interface.as_deref(),
+        timeout: Duration::from_secs(args.timeout),
+        filter: &filter,
+        sort: args.sort,
+        columns: &args.columns,
+    };
 
-    println!("🔍 Scanning for Shelly devices on the network (5s)...\n");
+    if args.watch {
+        return watch::watch(&search, args.output).await;
+    }
 
-    let allowed_types: Option<HashSet<String>> = args
-        .r#type
-        .map(|s| s.split(',').map(|t| t.trim().to_string()).collect());
+    match args.output {
+        BrowseOutput::Table => browse_table(&search).await?,
+        BrowseOutput::Ndjson => {
+            search
+                .run(|_, device| {
+                    println!("{}", serde_json::to_string(device)?);
+                    Ok(())
+                })
+                .await?;
+        }
+        BrowseOutput::Json => {
+            let devices = search.run(|_, _| Ok(())).await?;
+            let devices = ordered(&devices, search.sort);
+            println!("{}", serde_json::to_string_pretty(&devices)?);
+        }
+        BrowseOutput::Csv => {
+            let devices = search.run(|_, _| Ok(())).await?;
+            let mut writer = csv::Writer::from_writer(stdout());
+            for (i, device) in ordered(&devices, search.sort).into_iter().enumerate() {
+                let Fields(fields) = serde_json::from_str(&serde_json::to_string(device)?)?;
+                if i == 0 {
+                    writer.write_record(fields.iter().map(|(key, _)| key))?;
+                }
+                writer.write_record(fields.iter().map(|(_, value)| csv_cell(value)))?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Where and how to look for devices: mDNS for `timeout`, or a subnet scan.
+struct Search<'a> {
+    scan: Option<&'a str>,
+    interface: Option<&'a str>,
+    timeout: Duration,
+    filter: &'a Filter,
+    sort: BrowseSort,
+    columns: &'a [BrowseColumn],
+}
+
+impl Search<'_> {
+    /// Finds devices and remembers them for resolving names later.
+    async fn run<F>(&self, on_found: F) -> Result<BTreeMap<String, ShellyDevice>>
+    where
+        F: FnMut(&BTreeMap<String, ShellyDevice>, &ShellyDevice) -> Result<()>,
+    {
+        let devices = self.find(on_found).await?;
+        resolve::remember(devices.values().map(cache_entry));
+        Ok(devices)
+    }
+
+    /// Finds devices without updating the cache.
+    async fn find<F>(&self, on_found: F) -> Result<BTreeMap<String, ShellyDevice>>
+    where
+        F: FnMut(&BTreeMap<String, ShellyDevice>, &ShellyDevice) -> Result<()>,
+    {
+        match self.scan {
+            Some(cidr) => scan::scan(cidr, self.filter, on_found).await,
+            None => discover(self.timeout, self.interface, self.filter, on_found).await,
+        }
+    }
+}
+
+/// Browses interactively on a terminal, or searches once otherwise, then prints
+/// the table of devices found.
+async fn browse_table(search: &Search<'_>) -> Result<()> {
+    match search.scan {
+        Some(cidr) => println!("🔍 Scanning {} for Shelly devices...\n", cidr),
+        None => println!(
+            "🔍 Scanning for Shelly devices on the network ({}s)...\n",
+            search.timeout.as_secs()
+        ),
+    }
+
+    let devices = if stdout().is_terminal() {
+        tui::run(search).await?
+    } else {
+        search.run(|_, _| Ok(())).await?
+    };
+    render_table(&ordered(&devices, search.sort), search.columns).printstd();
+    Ok(())
+}
+
+/// Redraws a table in place by moving the cursor back over the previous one.
+#[derive(Default)]
+struct Repaint {
+    last_height: usize,
+}
+
+impl Repaint {
+    fn draw(&mut self, table: &Table) -> Result<()> {
+        let mut stdout = stdout();
+
+        let height = table.to_string().lines().count();
+        if height > self.last_height {
+            for _ in 0..(height - self.last_height) {
+                println!();
+            }
+            self.last_height = height;
+        }
+
+        execute!(
+            stdout,
+            MoveUp(height as u16),
+            Clear(ClearType::FromCursorDown)
+        )?;
+
+        table.printstd();
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Browses mDNS for `timeout` and probes every responding Shelly device.
+///
+/// Hosts advertising `_shelly._tcp` are probed as they resolve. Plain
+/// `_http._tcp` hosts are only probed once the window closes, if they did not
+/// show up as Shellys by then, so Gen2+ devices get their TXT records used.
+///
+/// Probes run concurrently, at most `MAX_PROBES` at a time; those still in
+/// flight when the window closes are waited for. `on_found` is called with all
+/// devices found so far and the new one each time one is added.
+pub async fn discover<F>(
+    timeout: Duration,
+    interface: Option<&str>,
+    filter: &Filter,
+    mut on_found: F,
+) -> Result<BTreeMap<String, ShellyDevice>>
+where
+    F: FnMut(&BTreeMap<String, ShellyDevice>, &ShellyDevice) -> Result<()>,
+{
+    let mdns = daemon(interface)?;
+    let mut events = browse_services(&mdns)?;
 
     let client = Client::new();
-    let mut seen = HashSet::<IpAddr>::new();
+    let limit = Arc::new(Semaphore::new(MAX_PROBES));
+    let (tx, mut rx) = mpsc::unbounded_channel::<Option<ShellyDevice>>();
+    let spawn_probe = |device: ShellyDevice, gen1: bool| {
+        let (client, limit, tx) = (client.clone(), limit.clone(), tx.clone());
+        tokio::spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let device = if gen1 {
+                scan::probe_any(&client, device).await
+            } else {
+                probe(&client, device).await
+            };
+            let _ = tx.send(device);
+        });
+    };
+
+    let mut seen = HashSet::<String>::new();
+    let mut deferred = Vec::<ShellyDevice>::new();
     let mut devices = BTreeMap::<String, ShellyDevice>::new();
 
-    let start = Instant::now();
-    let timeout = Duration::from_secs(5);
-
-    let mut last_height = 0;
-    let mut stdout = stdout();
-
-    while start.elapsed() < timeout {
-        while let Ok(event) = receiver.try_recv() {
-            if let ServiceEvent::ServiceResolved(info) = event {
-                if let Some(ip) = info.get_addresses().iter().find(|a| a.is_ipv4()) {
-                    if seen.contains(ip) {
-                        continue;
-                    }
-                    seen.insert(*ip);
-                    let ip_str = ip.to_string();
-
-                    let url = format!("http://{}/rpc/Shelly.GetDeviceInfo", ip_str);
-                    if let Ok(resp) = client
-                        .get(&url)
-                        .timeout(Duration::from_secs(2))
-                        .send()
-                        .await
-                    {
-                        if let Ok(json) = resp.json::<Value>().await {
-                            let device_type = json["id"]
-                                .as_str()
-                                .unwrap_or("-")
-                                .to_string()
-                                .strip_prefix("shelly")
-                                .and_then(|s| s.split_once('-'))
-                                .map(|(model, _)| model)
-                                .unwrap_or("unknown")
-                                .to_string();
-
-                            if let Some(ref allowed) = allowed_types {
-                                if !allowed.contains(&device_type) {
-                                    continue;
-                                }
-                            }
-                            let gen = json["gen"].as_u64().unwrap_or(0) as u32;
-                            let hostname = info
-                                .get_hostname()
-                                .strip_suffix(".local.")
-                                .unwrap_or_else(|| info.get_hostname())
-                                .to_string();
-
-                            let get = |key: &str| {
-                                json.get(key)
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("-")
-                                    .to_string()
-                            };
-
-                            let mut ssid = "-".to_string();
-                            let mut rssi = 0;
-
-                            let status_url = format!("http://{}/rpc/Shelly.GetStatus", ip_str);
-                            if let Ok(status_resp) = client
-                                .get(&status_url)
-                                .timeout(Duration::from_secs(2))
-                                .send()
-                                .await
-                            {
-                                if let Ok(status_json) = status_resp.json::<Value>().await {
-                                    ssid = status_json
-                                        .pointer("/wifi/ssid")
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("-")
-                                        .to_string();
-
-                                    rssi = status_json
-                                        .pointer("/wifi/rssi")
-                                        .and_then(|v| v.as_i64())
-                                        .unwrap_or(0)
-                                        as i32;
-                                }
-                            }
-
-                            devices.insert(
-                                hostname.clone(),
-                                ShellyDevice {
-                                    hostname,
-                                    gen,
-                                    ip: ip_str,
-                                    name: get("name"),
-                                    ver: get("ver"),
-                                    app: get("app"),
-                                    profile: get("profile"),
-                                    ssid,
-                                    rssi,
-                                },
-                            );
-
-                            let mut table = Table::new();
-                            let format = FormatBuilder::new()
-                                .column_separator(' ')
-                                .borders('\0')
-                                .separator(
-                                    LinePosition::Top,
-                                    LineSeparator::new('\0', '\0', '\0', '\0'),
-                                )
-                                .separator(
-                                    LinePosition::Title,
-                                    LineSeparator::new('\0', '\0', '\0', '\0'),
-                                )
-                                .separator(
-                                    LinePosition::Bottom,
-                                    LineSeparator::new('\0', '\0', '\0', '\0'),
-                                )
-                                .padding(0, 0)
-                                .build();
-                            table.set_format(format);
-
-                            table.add_row(Row::new(vec![
-                                Cell::new("Hostname").style_spec("Fc"),
-                                Cell::new("IP Addr").style_spec("Fc"),
-                                Cell::new("SSID").style_spec("Fc"),
-                                Cell::new("RSSI").style_spec("Fc"),
-                                Cell::new("Gen").style_spec("Fc"),
-                                Cell::new("App").style_spec("Fc"),
-                                Cell::new("Profile").style_spec("Fc"),
-                                Cell::new("Firmware").style_spec("Fc"),
-                                Cell::new("Name").style_spec("Fc"),
-                            ]));
-
-                            for device in devices.values() {
-                                table.add_row(Row::new(vec![
-                                    Cell::new(&device.hostname).style_spec("Fg"),
-                                    Cell::new(&device.ip).style_spec("Fw"),
-                                    Cell::new(&device.ssid).style_spec("Fw"),
-                                    match device.rssi {
-                                        rssi if rssi >= -60 => {
-                                            Cell::new(&device.rssi.to_string()).style_spec("Fg")
-                                        }
-                                        rssi if rssi >= -75 => {
-                                            Cell::new(&device.rssi.to_string()).style_spec("Fy")
-                                        }
-                                        _ => Cell::new(&device.rssi.to_string()).style_spec("Fr"),
-                                    },
-                                    Cell::new(&device.gen.to_string()).style_spec("Fw"),
-                                    Cell::new(&device.app).style_spec("Fy"),
-                                    Cell::new(&device.profile).style_spec("Fw"),
-                                    Cell::new(&device.ver).style_spec("Fy"),
-                                    Cell::new(&device.name).style_spec("Fw"),
-                                ]));
-                            }
-
-                            let height = table.to_string().lines().count();
-                            if height > last_height {
-                                for _ in 0..(height - last_height) {
-                                    println!();
-                                }
-                                last_height = height;
-                            }
-
-                            execute!(
-                                stdout,
-                                MoveUp(height as u16),
-                                Clear(ClearType::FromCursorDown)
-                            )?;
-
-                            table.printstd();
-                            stdout.flush()?;
-                        }
-                    }
+    let mut add = |device: Option<ShellyDevice>| match device {
+        Some(device) => add_device(&mut devices, device, filter, &mut on_found),
+        None => Ok(()),
+    };
+
+    let deadline = sleep(timeout);
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = &mut deadline => break,
+            Some(device) = rx.recv() => add(device)?,
+            event = events.recv() => {
+                let info = match event {
+                    Some(ServiceEvent::ServiceResolved(info)) => info,
+                    Some(_) => continue,
+                    None => break,
+                };
+                let Some(device) = advertised(&info) else {
+                    continue;
+                };
+                if info.get_type() != SHELLY_SERVICE {
+                    deferred.push(device);
+                } else if seen.insert(device.hostname.clone()) {
+                    spawn_probe(device, false);
                 }
             }
         }
-        sleep(Duration::from_millis(100)).await;
     }
 
-    Ok(())
+    // Gen1 devices, and any whose `_shelly` advert never came
+    for device in deferred {
+        if seen.insert(device.hostname.clone()) {
+            spawn_probe(device, true);
+        }
+    }
+
+    // Every service resolved within the window still gets its result
+    drop(tx);
+    while let Some(device) = rx.recv().await {
+        add(device)?;
+    }
+    let _ = mdns.shutdown();
+
+    Ok(devices)
+}
+
+/// Starts an mDNS daemon, limited to one interface if given by name or address.
+fn daemon(interface: Option<&str>) -> Result<ServiceDaemon> {
+    let mdns = ServiceDaemon::new()?;
+    if let Some(interface) = interface {
+        let kind = match interface.parse::<IpAddr>() {
+            Ok(addr) => IfKind::Addr(addr),
+            Err(_) => IfKind::Name(interface.to_string()),
+        };
+        mdns.disable_interface(IfKind::All)?;
+        mdns.enable_interface(kind)?;
+    }
+    Ok(mdns)
+}
+
+/// Browses `_shelly._tcp` and `_http._tcp` into a single stream of events.
+fn browse_services(mdns: &ServiceDaemon) -> Result<mpsc::UnboundedReceiver<ServiceEvent>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    for service_type in [SHELLY_SERVICE, HTTP_SERVICE] {
+        let receiver = mdns.browse(service_type)?;
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = receiver.recv_async().await {
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    Ok(rx)
+}
+
+/// Seeds a device from a resolved service; `None` if it cannot be a Shelly.
+///
+/// `_shelly._tcp` TXT records fill in generation, app and firmware. Plain
+/// `_http._tcp` services are only kept when the host is named like a Shelly,
+/// which Gen1 devices always are.
+///
+/// mDNS gives no zone for link-local IPv6 addresses, so they are listed but
+/// never used as `ip`; a device with nothing else cannot be reached.
+fn advertised(info: &ServiceInfo) -> Option<ShellyDevice> {
+    let mut addresses: Vec<IpAddr> = info.get_addresses().iter().copied().collect();
+    addresses.sort_by_key(|ip| (reachability(ip), *ip));
+    let hostname = info
+        .get_hostname()
+        .strip_suffix(".local.")
+        .unwrap_or_else(|| info.get_hostname());
+    let Some(ip) = addresses.first().copied().filter(|ip| reachability(ip) < 2) else {
+        debug!("Skipping {}: only link-local addresses", hostname);
+        return None;
+    };
+    let mut device = ShellyDevice::new(hostname.to_string(), ip);
+    device.addresses = addresses;
+
+    if info.get_type() == SHELLY_SERVICE {
+        let txt = |key: &str| info.get_property_val_str(key).filter(|v| !v.is_empty());
+        device.gen = txt("gen").and_then(|g| g.parse().ok()).unwrap_or(0);
+        if let Some(app) = txt("app") {
+            device.app = app.to_string();
+        }
+        if let Some(ver) = txt("ver") {
+            device.ver = ver.to_string();
+        }
+    } else if !hostname.to_ascii_lowercase().starts_with("shelly") {
+        return None;
+    }
+    Some(device)
+}
+
+/// Orders addresses by how likely they are to work without a zone: IPv4,
+/// then routable IPv6, then link-local IPv6.
+fn reachability(ip: &IpAddr) -> u8 {
+    match ip {
+        IpAddr::V4(_) => 0,
+        IpAddr::V6(v6) if !v6.is_unicast_link_local() => 1,
+        IpAddr::V6(_) => 2,
+    }
+}
+
+/// Records `device` unless it is filtered out, then reports it to `on_found`.
+fn add_device<F>(
+    devices: &mut BTreeMap<String, ShellyDevice>,
+    device: ShellyDevice,
+    filter: &Filter,
+    on_found: &mut F,
+) -> Result<()>
+where
+    F: FnMut(&BTreeMap<String, ShellyDevice>, &ShellyDevice) -> Result<()>,
+{
+    if !filter.matches(&device) {
+        return Ok(());
+    }
+    devices.insert(device.hostname.clone(), device.clone());
+    on_found(devices, &device)
+}
+
+/// Model part of a device id, e.g. `plus1pm` for `shellyplus1pm-a8032ab12345`.
+fn device_type(id: &str) -> String {
+    id.strip_prefix("shelly")
+        .and_then(|s| s.split_once('-'))
+        .map(|(model, _)| model)
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+/// Fills in what `device` lacks from device info and Wi-Fi status; `None` if
+/// the host is not a Shelly.
+///
+/// Fields already known, e.g. from TXT records, are kept. Without a hostname
+/// the device id is used, as Shelly does by default.
+async fn probe(client: &Client, mut device: ShellyDevice) -> Option<ShellyDevice> {
+    let url = rpc_url(&device.ip, "Shelly.GetDeviceInfo");
+    let json = client
+        .get(&url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .json::<Value>()
+        .await
+        .ok()?;
+
+    let fill = |field: &mut String, key: &str| {
+        if field == "-" {
+            if let Some(value) = json.get(key).and_then(|v| v.as_str()) {
+                *field = value.to_string();
+            }
+        }
+    };
+    fill(&mut device.id, "id");
+    fill(&mut device.mac, "mac");
+    fill(&mut device.model, "model");
+    fill(&mut device.app, "app");
+    fill(&mut device.profile, "profile");
+    fill(&mut device.ver, "ver");
+    fill(&mut device.name, "name");
+    if device.gen == 0 {
+        device.gen = json["gen"].as_u64().unwrap_or(0) as u32;
+    }
+    if device.hostname.is_empty() {
+        device.hostname = device.id.clone();
+    }
+
+    let status_url = rpc_url(&device.ip, "Shelly.GetStatus");
+    if let Ok(status_resp) = client.get(&status_url).timeout(PROBE_TIMEOUT).send().await {
+        if let Ok(status_json) = status_resp.json::<Value>().await {
+            device.ssid = status_json
+                .pointer("/wifi/ssid")
+                .and_then(|v| v.as_str())
+                .unwrap_or("-")
+                .to_string();
+
+            device.rssi = status_json
+                .pointer("/wifi/rssi")
+                .and_then(|v| v.as_i64())
+                .unwrap_or(0) as i32;
+
+            read_health(&mut device, &status_json);
+        }
+    }
+
+    Some(device)
+}
+
+/// Reads the health fields from a `Shelly.GetStatus` response.
+fn read_health(device: &mut ShellyDevice, status: &Value) {
+    let flag = |pointer: &str| status.pointer(pointer).and_then(|v| v.as_bool());
+
+    device.uptime = status.pointer("/sys/uptime").and_then(|v| v.as_u64());
+    // Switches, covers and the like report the device's internal temperature
+    device.temperature = status
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(_, component)| component.pointer("/temperature/tC"))
+        .filter_map(|v| v.as_f64())
+        .reduce(f64::max);
+    device.update = ["stable", "beta"]
+        .iter()
+        .find_map(|channel| {
+            let pointer = format!("/sys/available_updates/{}/version", channel);
+            status.pointer(&pointer).and_then(|v| v.as_str())
+        })
+        .map(String::from);
+    device.cloud = flag("/cloud/connected");
+    device.mqtt = flag("/mqtt/connected");
+    device.restart_required = flag("/sys/restart_required");
+}
+
+fn cache_entry(device: &ShellyDevice) -> (String, Cached) {
+    let cached = Cached {
+        ip: device.ip.clone(),
+        id: device.id.clone(),
+    };
+    (device.hostname.clone(), cached)
+}
+
+/// Devices in display order.
+fn ordered(devices: &BTreeMap<String, ShellyDevice>, by: BrowseSort) -> Vec<&ShellyDevice> {
+    let mut devices: Vec<_> = devices.values().collect();
+    filter::sort_by(&mut devices, by, |d| d);
+    devices
+}
+
+fn render_table(devices: &[&ShellyDevice], columns: &[BrowseColumn]) -> Table {
+    let mut table = plain_table();
+
+    let mut header = Row::new(vec![
+        Cell::new("Hostname").style_spec("Fc"),
+        Cell::new("IP Addr").style_spec("Fc"),
+        Cell::new("SSID").style_spec("Fc"),
+        Cell::new("RSSI").style_spec("Fc"),
+        Cell::new("Gen").style_spec("Fc"),
+        Cell::new("App").style_spec("Fc"),
+        Cell::new("Profile").style_spec("Fc"),
+        Cell::new("Firmware").style_spec("Fc"),
+        Cell::new("Name").style_spec("Fc"),
+    ]);
+    for column in columns {
+        header.add_cell(Cell::new(column_title(*column)).style_spec("Fc"));
+    }
+    table.add_row(header);
+
+    for device in devices {
+        let mut row = Row::new(vec![
+            Cell::new(&device.hostname).style_spec("Fg"),
+            Cell::new(&addresses(device)).style_spec("Fw"),
+            Cell::new(&device.ssid).style_spec("Fw"),
+            match device.rssi {
+                rssi if rssi >= -60 => Cell::new(&device.rssi.to_string()).style_spec("Fg"),
+                rssi if rssi >= -75 => Cell::new(&device.rssi.to_string()).style_spec("Fy"),
+                _ => Cell::new(&device.rssi.to_string()).style_spec("Fr"),
+            },
+            Cell::new(&device.gen.to_string()).style_spec("Fw"),
+            Cell::new(&device.app).style_spec("Fy"),
+            Cell::new(&device.profile).style_spec("Fw"),
+            Cell::new(&device.ver).style_spec("Fy"),
+            Cell::new(&device.name).style_spec("Fw"),
+        ]);
+        for column in columns {
+            row.add_cell(health_cell(device, *column));
+        }
+        table.add_row(row);
+    }
+
+    table
+}
+
+fn column_title(column: BrowseColumn) -> &'static str {
+    match column {
+        BrowseColumn::Uptime => "Uptime",
+        BrowseColumn::Temp => "Temp",
+        BrowseColumn::Update => "Update",
+        BrowseColumn::Cloud => "Cloud",
+        BrowseColumn::Mqtt => "MQTT",
+        BrowseColumn::Restart => "Restart",
+    }
+}
+
+fn health_cell(device: &ShellyDevice, column: BrowseColumn) -> Cell {
+    // Connected is good, but a pending update or restart needs attention
+    let flag = |value: Option<bool>, good: bool| match value {
+        Some(v) if v == good => Cell::new(if v { "yes" } else { "no" }).style_spec("Fg"),
+        Some(v) => Cell::new(if v { "yes" } else { "no" }).style_spec("Fr"),
+        None => Cell::new("-"),
+    };
+
+    match column {
+        BrowseColumn::Uptime => match device.uptime {
+            Some(secs) => Cell::new(&format_uptime(secs)).style_spec("Fw"),
+            None => Cell::new("-"),
+        },
+        BrowseColumn::Temp => match device.temperature {
+            Some(t) if t >= 80.0 => Cell::new(&format!("{:.1}°C", t)).style_spec("Fr"),
+            Some(t) if t >= 60.0 => Cell::new(&format!("{:.1}°C", t)).style_spec("Fy"),
+            Some(t) => Cell::new(&format!("{:.1}°C", t)).style_spec("Fw"),
+            None => Cell::new("-"),
+        },
+        BrowseColumn::Update => match &device.update {
+            Some(ver) => Cell::new(ver).style_spec("Fy"),
+            None => Cell::new("-"),
+        },
+        BrowseColumn::Cloud => flag(device.cloud, true),
+        BrowseColumn::Mqtt => flag(device.mqtt, true),
+        BrowseColumn::Restart => flag(device.restart_required, false),
+    }
+}
+
+/// Compact uptime, e.g. `3d 4h`, `5h 12m` or `42s`.
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+/// All addresses of a device, one per line.
+fn addresses(device: &ShellyDevice) -> String {
+    let addresses: Vec<String> = device.addresses.iter().map(IpAddr::to_string).collect();
+    addresses.join("\n")
 }
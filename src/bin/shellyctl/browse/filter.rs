@@ -0,0 +1,112 @@
+use super::{device_type, ShellyDevice};
+use crate::cli::{BrowseArgs, BrowseSort};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use semver::{Version, VersionReq};
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+/// Which devices browse keeps, built from its filter options.
+///
+/// An empty filter keeps everything.
+#[derive(Default)]
+pub struct Filter {
+    types: Option<HashSet<String>>,
+    gens: Vec<u32>,
+    apps: Vec<String>,
+    fw: Option<VersionReq>,
+    ssid: Option<String>,
+    name: Option<Regex>,
+    rssi_below: Option<i32>,
+}
+
+impl Filter {
+    pub fn new(args: &BrowseArgs) -> Result<Self> {
+        let fw = args
+            .fw
+            .as_deref()
+            .map(|req| VersionReq::parse(req).map_err(|e| anyhow!("Invalid --fw '{}': {}", req, e)))
+            .transpose()?;
+        let name = args
+            .name
+            .as_deref()
+            .map(|re| Regex::new(re).map_err(|e| anyhow!("Invalid --name regex: {}", e)))
+            .transpose()?;
+
+        Ok(Filter {
+            types: args
+                .r#type
+                .as_ref()
+                .map(|s| s.split(',').map(|t| t.trim().to_string()).collect()),
+            gens: args.gen.clone(),
+            apps: args.app.iter().map(|a| a.to_ascii_lowercase()).collect(),
+            fw,
+            ssid: args.ssid.clone(),
+            name,
+            rssi_below: args.rssi_below,
+        })
+    }
+
+    pub fn matches(&self, device: &ShellyDevice) -> bool {
+        if let Some(types) = &self.types {
+            if !types.contains(&device_type(&device.id)) {
+                return false;
+            }
+        }
+        if !self.gens.is_empty() && !self.gens.contains(&device.gen) {
+            return false;
+        }
+        if !self.apps.is_empty() && !self.apps.contains(&device.app.to_ascii_lowercase()) {
+            return false;
+        }
+        if let Some(req) = &self.fw {
+            // Unknown firmware never matches; betas only match requirements naming one
+            if !firmware(&device.ver).is_some_and(|v| req.matches(&v)) {
+                return false;
+            }
+        }
+        if self.ssid.as_ref().is_some_and(|ssid| *ssid != device.ssid) {
+            return false;
+        }
+        if self
+            .name
+            .as_ref()
+            .is_some_and(|re| !re.is_match(&device.name))
+        {
+            return false;
+        }
+        // 0 means the signal strength is unknown
+        if let Some(threshold) = self.rssi_below {
+            if device.rssi == 0 || device.rssi >= threshold {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Parses a firmware version like `1.4.0` or `1.5.0-beta1`.
+fn firmware(ver: &str) -> Option<Version> {
+    Version::parse(ver.trim_start_matches('v')).ok()
+}
+
+/// Orders `items` for display; equal ones keep their order, i.e. by hostname.
+///
+/// Weakest signal and oldest firmware come first, unknown values last.
+pub fn sort_by<T>(items: &mut [T], by: BrowseSort, device: impl Fn(&T) -> &ShellyDevice) {
+    match by {
+        BrowseSort::Hostname => {}
+        BrowseSort::Rssi => items.sort_by_key(|item| {
+            let rssi = device(item).rssi;
+            (rssi == 0, rssi)
+        }),
+        BrowseSort::Ip => items.sort_by_key(|item| {
+            let ip = device(item).ip.parse::<IpAddr>().ok();
+            (ip.is_none(), ip)
+        }),
+        BrowseSort::Fw => items.sort_by_key(|item| {
+            let ver = firmware(&device(item).ver);
+            (ver.is_none(), ver)
+        }),
+    }
+}
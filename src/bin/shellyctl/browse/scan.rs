@@ -0,0 +1,164 @@
+use super::{add_device, probe, Filter, ShellyDevice};
+use crate::rpc::device_url;
+use anyhow::{anyhow, bail, Result};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Addresses probed at once; most of them usually do not answer.
+const SCAN_CONCURRENCY: usize = 128;
+
+/// How long an address gets to answer `/shelly` before it is skipped.
+const SCAN_TIMEOUT: Duration = Duration::from_millis(800);
+
+/// Largest subnet scanned, a /16.
+const MAX_HOSTS: u32 = 1 << 16;
+
+/// Probes every host address in `cidr` for a Shelly device.
+///
+/// `/shelly` answers on all generations; Gen2+ devices are then queried over
+/// RPC like browsed ones, Gen1 devices through their HTTP API.
+pub async fn scan<F>(
+    cidr: &str,
+    filter: &Filter,
+    mut on_found: F,
+) -> Result<BTreeMap<String, ShellyDevice>>
+where
+    F: FnMut(&BTreeMap<String, ShellyDevice>, &ShellyDevice) -> Result<()>,
+{
+    let client = Client::new();
+    let limit = Arc::new(Semaphore::new(SCAN_CONCURRENCY));
+
+    let mut tasks = JoinSet::new();
+    for ip in hosts(cidr)? {
+        let (client, limit) = (client.clone(), limit.clone());
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let device = ShellyDevice::new(String::new(), IpAddr::V4(ip));
+            probe_any(&client, device).await
+        });
+    }
+
+    let mut devices = BTreeMap::new();
+    while let Some(result) = tasks.join_next().await {
+        if let Some(device) = result? {
+            add_device(&mut devices, device, filter, &mut on_found)?;
+        }
+    }
+    Ok(devices)
+}
+
+/// Host addresses of an IPv4 CIDR, without network and broadcast addresses.
+fn hosts(cidr: &str) -> Result<Vec<Ipv4Addr>> {
+    let (addr, prefix) = cidr
+        .split_once('/')
+        .ok_or_else(|| anyhow!("'{}' is not a CIDR like 10.0.20.0/24", cidr))?;
+    let addr: Ipv4Addr = addr.parse().map_err(|e| anyhow!("'{}': {}", cidr, e))?;
+    let prefix: u32 = prefix
+        .parse()
+        .ok()
+        .filter(|p| *p <= 32)
+        .ok_or_else(|| anyhow!("'{}': prefix must be 0-32", cidr))?;
+
+    let size = 1u64 << (32 - prefix);
+    if size > MAX_HOSTS as u64 {
+        bail!("'{}' is too large to scan, use /16 or smaller", cidr);
+    }
+
+    let network = u32::from(addr) & (u32::MAX.checked_shl(32 - prefix).unwrap_or(0));
+    let range = match size {
+        1 | 2 => 0..size as u32,
+        _ => 1..size as u32 - 1,
+    };
+    Ok(range.map(|i| Ipv4Addr::from(network + i)).collect())
+}
+
+async fn get_json(client: &Client, url: &str, timeout: Duration) -> Option<Value> {
+    let resp = client.get(url).timeout(timeout).send().await.ok()?;
+    resp.json::<Value>().await.ok()
+}
+
+/// Probes a host of unknown generation, asking `/shelly` which API it speaks.
+pub(super) async fn probe_any(client: &Client, device: ShellyDevice) -> Option<ShellyDevice> {
+    let url = device_url(&device.ip, "shelly");
+    let shelly = get_json(client, &url, SCAN_TIMEOUT).await?;
+
+    if shelly["gen"].as_u64().unwrap_or(1) >= 2 {
+        probe(client, device).await
+    } else if shelly["type"].is_string() {
+        Some(probe_gen1(client, device, &shelly).await)
+    } else {
+        None
+    }
+}
+
+/// Fills a device from the Gen1 `/shelly`, `/settings` and `/status` endpoints.
+async fn probe_gen1(client: &Client, mut device: ShellyDevice, shelly: &Value) -> ShellyDevice {
+    let timeout = Duration::from_secs(2);
+    let settings = get_json(client, &device_url(&device.ip, "settings"), timeout)
+        .await
+        .unwrap_or(Value::Null);
+    let status = get_json(client, &device_url(&device.ip, "status"), timeout)
+        .await
+        .unwrap_or(Value::Null);
+
+    let text = |value: Option<&Value>| value.and_then(|v| v.as_str()).unwrap_or("-").to_string();
+
+    device.ver = shelly["fw"]
+        .as_str()
+        .and_then(gen1_version)
+        .unwrap_or("-")
+        .to_string();
+
+    if let Some(hostname) = settings
+        .pointer("/device/hostname")
+        .and_then(|v| v.as_str())
+    {
+        device.id = hostname.to_string();
+    }
+    if device.hostname.is_empty() {
+        device.hostname = match device.id.as_str() {
+            "-" => device.ip.clone(),
+            id => id.to_string(),
+        };
+    }
+
+    device.mac = text(shelly.get("mac"));
+    device.model = text(shelly.get("type"));
+    device.gen = 1;
+    device.profile = text(settings.get("mode"));
+    device.name = text(settings.get("name"));
+    device.ssid = text(settings.pointer("/wifi_sta/ssid"));
+    device.rssi = status
+        .pointer("/wifi_sta/rssi")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0) as i32;
+
+    let flag = |pointer: &str| status.pointer(pointer).and_then(|v| v.as_bool());
+    device.uptime = status["uptime"].as_u64();
+    device.temperature = status
+        .pointer("/tmp/tC")
+        .or_else(|| status.get("temperature"))
+        .and_then(|v| v.as_f64());
+    device.update = status
+        .pointer("/update/new_version")
+        .filter(|_| flag("/update/has_update") == Some(true))
+        .and_then(|v| v.as_str())
+        .and_then(gen1_version)
+        .map(String::from);
+    device.cloud = flag("/cloud/connected");
+    device.mqtt = flag("/mqtt/connected");
+    device
+}
+
+/// Version part of a Gen1 firmware string, e.g. `1.14.0` in
+/// `20230913-114008/v1.14.0-gcb84623`.
+fn gen1_version(fw: &str) -> Option<&str> {
+    let version = fw.rsplit('/').next()?.trim_start_matches('v');
+    version.split(['-', '@']).next().filter(|v| !v.is_empty())
+}
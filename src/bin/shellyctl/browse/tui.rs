@@ -0,0 +1,700 @@
+use super::{cache_entry, column_title, health_cell, ordered, Search, ShellyDevice, PROBE_TIMEOUT};
+use crate::cli::BrowseColumn;
+use crate::config::dump::fetch_config;
+use crate::console;
+use crate::resolve;
+use crate::rpc::{call, device_url};
+use anyhow::{anyhow, Result};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{stdout, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+
+/// How often key presses are picked up.
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+const HELP: &str =
+    "↑↓ select  i/c/s status/config/scripts  PgUp/PgDn scroll  t toggle  r reboot  w web UI  q quit";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Status,
+    Config,
+    Scripts,
+}
+
+impl Pane {
+    fn title(self) -> &'static str {
+        match self {
+            Pane::Status => "Status",
+            Pane::Config => "Config",
+            Pane::Scripts => "Scripts",
+        }
+    }
+
+    fn next(self) -> Pane {
+        match self {
+            Pane::Status => Pane::Config,
+            Pane::Config => Pane::Scripts,
+            Pane::Scripts => Pane::Status,
+        }
+    }
+}
+
+/// A switch, relay or light that can be toggled, e.g. `switch:1`.
+#[derive(Clone, Copy)]
+struct Output {
+    kind: &'static str,
+    id: u64,
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.id)
+    }
+}
+
+/// Results of work spawned off the UI loop.
+enum Update {
+    Loaded {
+        hostname: String,
+        pane: Pane,
+        lines: Result<Vec<String>, String>,
+        /// Outputs found in a loaded status, for toggling.
+        outputs: Option<Vec<Output>>,
+    },
+    Message(String),
+}
+
+/// A question in the message line, answered by the next key press.
+enum Prompt {
+    Reboot,
+    Toggle(Vec<Output>),
+}
+
+/// Switches the terminal to a raw alternate screen until dropped.
+///
+/// Log output goes to `log` meanwhile, as writing to stderr would garble the screen.
+struct Screen;
+
+impl Screen {
+    fn enter(log: mpsc::UnboundedSender<String>) -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        console::divert(Some(log));
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        console::divert(None);
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Browses interactively until the user quits, returning every device found.
+///
+/// Discovery keeps running in the background; the side pane shows status,
+/// config or scripts of the selected device, loaded as the selection moves.
+pub(super) async fn run(search: &Search<'_>) -> Result<BTreeMap<String, ShellyDevice>> {
+    let (found_tx, mut found_rx) = mpsc::unbounded_channel();
+    let discovery = search.find(move |_, device| {
+        let _ = found_tx.send(device.clone());
+        Ok(())
+    });
+    tokio::pin!(discovery);
+
+    let client = Client::builder().timeout(PROBE_TIMEOUT).build()?;
+    let (tx, mut rx) = mpsc::unbounded_channel::<Update>();
+    let (log_tx, mut log_rx) = mpsc::unbounded_channel::<String>();
+    let mut app = App::new(search);
+    let mut ticker = interval(INPUT_POLL);
+
+    let screen = Screen::enter(log_tx)?;
+    app.draw()?;
+
+    'ui: loop {
+        tokio::select! {
+            result = &mut discovery, if app.searching => {
+                result?;
+                app.searching = false;
+                if let Err(err) = resolve::save(app.devices.values().map(cache_entry)) {
+                    app.message = format!("⚠️  Failed to update the device cache: {}", err);
+                }
+            }
+            Some(device) = found_rx.recv() => app.add(device),
+            Some(update) = rx.recv() => app.apply(update),
+            Some(line) = log_rx.recv() => app.message = line,
+            _ = ticker.tick() => {
+                let mut changed = false;
+                while event::poll(Duration::ZERO)? {
+                    match event::read()? {
+                        Event::Key(key) if key.kind == KeyEventKind::Press => {
+                            if !app.on_key(key, &client, &tx) {
+                                break 'ui;
+                            }
+                            changed = true;
+                        }
+                        Event::Resize(_, _) => changed = true,
+                        _ => {}
+                    }
+                }
+                if !changed {
+                    continue;
+                }
+            }
+        }
+        app.load_pane(&client, &tx);
+        app.draw()?;
+    }
+    drop(screen);
+
+    // Quitting early cancels discovery before it updates the cache
+    if app.searching {
+        resolve::remember(app.devices.values().map(cache_entry));
+    }
+    Ok(app.devices)
+}
+
+struct App<'a> {
+    search: &'a Search<'a>,
+    searching: bool,
+    devices: BTreeMap<String, ShellyDevice>,
+    selected: Option<String>,
+    /// First visible row of the device list.
+    offset: usize,
+    pane: Pane,
+    loaded: Option<(String, Pane)>,
+    lines: Result<Vec<String>, String>,
+    scroll: usize,
+    /// Toggleable outputs by hostname, from the last status loaded.
+    outputs: HashMap<String, Vec<Output>>,
+    prompt: Option<Prompt>,
+    message: String,
+}
+
+impl<'a> App<'a> {
+    fn new(search: &'a Search<'a>) -> Self {
+        App {
+            search,
+            searching: true,
+            devices: BTreeMap::new(),
+            selected: None,
+            offset: 0,
+            pane: Pane::Status,
+            loaded: None,
+            lines: Ok(Vec::new()),
+            scroll: 0,
+            outputs: HashMap::new(),
+            prompt: None,
+            message: String::new(),
+        }
+    }
+
+    fn add(&mut self, device: ShellyDevice) {
+        if self.selected.is_none() {
+            self.selected = Some(device.hostname.clone());
+        }
+        self.devices.insert(device.hostname.clone(), device);
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Loaded {
+                hostname,
+                pane,
+                lines,
+                outputs,
+            } => {
+                if let Some(outputs) = outputs {
+                    self.outputs.insert(hostname.clone(), outputs);
+                }
+                if self.loaded == Some((hostname, pane)) {
+                    self.lines = lines;
+                }
+            }
+            Update::Message(message) => {
+                self.message = message;
+                // Show the outcome of an action, e.g. a toggled output
+                self.loaded = None;
+            }
+        }
+    }
+
+    fn current(&self) -> Option<&ShellyDevice> {
+        self.devices.get(self.selected.as_ref()?)
+    }
+
+    /// Moves the selection by `delta` rows in display order.
+    fn select(&mut self, delta: isize) {
+        let order: Vec<String> = ordered(&self.devices, self.search.sort)
+            .into_iter()
+            .map(|d| d.hostname.clone())
+            .collect();
+        let Some(last) = order.len().checked_sub(1) else {
+            return;
+        };
+        let index = self
+            .selected
+            .as_ref()
+            .and_then(|h| order.iter().position(|o| o == h))
+            .unwrap_or(0);
+        let index = index.saturating_add_signed(delta).min(last);
+        self.selected = Some(order[index].clone());
+    }
+
+    /// Handles a key press; `false` means quit.
+    fn on_key(
+        &mut self,
+        key: KeyEvent,
+        client: &Client,
+        tx: &mpsc::UnboundedSender<Update>,
+    ) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+        let device = self.current().cloned();
+
+        if let Some(prompt) = self.prompt.take() {
+            let Some(device) = device else {
+                return true;
+            };
+            match prompt {
+                Prompt::Reboot if key.code == KeyCode::Char('y') => {
+                    spawn_action(client, &device, Action::Reboot, tx);
+                    self.message = format!("Rebooting {}...", device.hostname);
+                }
+                Prompt::Reboot => self.message = "Reboot cancelled".to_string(),
+                Prompt::Toggle(outputs) => {
+                    let picked = match key.code {
+                        KeyCode::Char(c) => c.to_digit(10).and_then(|i| outputs.get(i as usize)),
+                        _ => None,
+                    };
+                    match picked {
+                        Some(output) => {
+                            spawn_action(client, &device, Action::Toggle(*output), tx);
+                            self.message = format!("Toggling {}...", output);
+                        }
+                        None => self.message = "Toggle cancelled".to_string(),
+                    }
+                }
+            }
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.select(1),
+            KeyCode::Home => self.select(isize::MIN),
+            KeyCode::End => self.select(isize::MAX),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll += 10,
+            KeyCode::Tab => self.pane = self.pane.next(),
+            KeyCode::Char('i') => self.pane = Pane::Status,
+            KeyCode::Char('c') => self.pane = Pane::Config,
+            KeyCode::Char('s') => self.pane = Pane::Scripts,
+            KeyCode::Enter => self.loaded = None,
+            KeyCode::Char('t') => {
+                if let Some(device) = device {
+                    self.toggle(&device, client, tx);
+                }
+            }
+            KeyCode::Char('r') => {
+                if let Some(device) = device {
+                    self.message = format!("Reboot {}? [y/N]", device.hostname);
+                    self.prompt = Some(Prompt::Reboot);
+                }
+            }
+            KeyCode::Char('w') => {
+                if let Some(device) = device {
+                    let url = device_url(&device.ip, "");
+                    self.message = match open_url(&url) {
+                        Ok(()) => format!("Opened {}", url),
+                        Err(e) => format!("Failed to open {}: {}", url, e),
+                    };
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Toggles the only output of `device`, or asks which one if it has several.
+    fn toggle(
+        &mut self,
+        device: &ShellyDevice,
+        client: &Client,
+        tx: &mpsc::UnboundedSender<Update>,
+    ) {
+        match self.outputs.get(&device.hostname).map(Vec::as_slice) {
+            None => {
+                // Outputs are only known once a status has been loaded
+                self.pane = Pane::Status;
+                self.loaded = None;
+                self.message = format!("Loading the status of {}, press t again", device.hostname);
+            }
+            Some([]) => {
+                self.message = format!("{} has no switch or light to toggle", device.hostname)
+            }
+            Some([output]) => {
+                spawn_action(client, device, Action::Toggle(*output), tx);
+                self.message = format!("Toggling {}...", output);
+            }
+            Some(outputs) => {
+                let choices: Vec<String> = outputs
+                    .iter()
+                    .take(10)
+                    .enumerate()
+                    .map(|(i, output)| format!("[{}] {}", i, output))
+                    .collect();
+                self.message = format!("Toggle which output? {}", choices.join("  "));
+                self.prompt = Some(Prompt::Toggle(outputs.to_vec()));
+            }
+        }
+    }
+
+    /// Starts loading the pane of the selected device unless it is already shown.
+    fn load_pane(&mut self, client: &Client, tx: &mpsc::UnboundedSender<Update>) {
+        let Some(device) = self.current() else {
+            return;
+        };
+        let wanted = (device.hostname.clone(), self.pane);
+        if self.loaded.as_ref() == Some(&wanted) {
+            return;
+        }
+
+        let (client, tx, pane) = (client.clone(), tx.clone(), self.pane);
+        let (hostname, ip, gen) = (device.hostname.clone(), device.ip.clone(), device.gen);
+        tokio::spawn(async move {
+            let (lines, outputs) = match pane_content(&client, &ip, gen, pane).await {
+                Ok((lines, outputs)) => (Ok(lines), outputs),
+                Err(e) => (Err(e.to_string()), None),
+            };
+            let _ = tx.send(Update::Loaded {
+                hostname,
+                pane,
+                lines,
+                outputs,
+            });
+        });
+
+        if self.loaded.as_ref().map(|(h, _)| h) != Some(&wanted.0) || self.lines.is_err() {
+            self.scroll = 0;
+        }
+        self.loaded = Some(wanted);
+        self.lines = Ok(vec!["Loading...".to_string()]);
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let (cols, rows) = terminal::size()?;
+        let (cols, rows) = (cols as usize, rows as usize);
+        let columns = self.search.columns;
+        let list_width = list_width(columns).min(cols * 3 / 5).max(30.min(cols));
+        let pane_width = cols.saturating_sub(list_width + 1);
+        // Title and header on top, message and help at the bottom
+        let body = rows.saturating_sub(4);
+
+        let devices = ordered(&self.devices, self.search.sort);
+        let selected = self
+            .selected
+            .as_ref()
+            .and_then(|h| devices.iter().position(|d| &d.hostname == h));
+        if let Some(index) = selected {
+            if index < self.offset {
+                self.offset = index;
+            } else if body > 0 && index >= self.offset + body {
+                self.offset = index + 1 - body;
+            }
+        }
+
+        let pane_lines: Vec<String> = match &self.lines {
+            Ok(lines) => lines.clone(),
+            Err(e) => vec![format!("Error: {}", e)],
+        };
+        self.scroll = self.scroll.min(pane_lines.len().saturating_sub(1));
+
+        let mut out = stdout();
+        let status = if self.searching {
+            format!("Searching for Shelly devices... {} found", devices.len())
+        } else {
+            format!("{} Shelly device(s)", devices.len())
+        };
+        line(&mut out, 0, 0, cols, &status, false)?;
+
+        line(&mut out, 0, 1, list_width, &list_header(columns), false)?;
+        let pane_title = match self.current() {
+            Some(device) => format!("{} of {}", self.pane.title(), device.hostname),
+            None => self.pane.title().to_string(),
+        };
+        line(&mut out, list_width + 1, 1, pane_width, &pane_title, false)?;
+
+        for row in 0..body {
+            let y = row + 2;
+            let text = match devices.get(self.offset + row) {
+                Some(device) => list_row(device, columns),
+                None => String::new(),
+            };
+            let highlight = selected == Some(self.offset + row);
+            line(&mut out, 0, y, list_width, &text, highlight)?;
+
+            let text = pane_lines.get(self.scroll + row).map_or("", String::as_str);
+            line(&mut out, list_width + 1, y, pane_width, text, false)?;
+        }
+
+        line(
+            &mut out,
+            0,
+            rows.saturating_sub(2),
+            cols,
+            &self.message,
+            false,
+        )?;
+        line(&mut out, 0, rows.saturating_sub(1), cols, HELP, false)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+/// Width of a `--columns` health column in the device list.
+fn column_width(column: BrowseColumn) -> usize {
+    match column {
+        BrowseColumn::Update => 11,
+        _ => 7,
+    }
+}
+
+fn list_width(columns: &[BrowseColumn]) -> usize {
+    let base = "Hostname".len().max(28) + 1 + 15 + 1 + 3 + 1 + 4;
+    base + columns.iter().map(|&c| column_width(c) + 1).sum::<usize>()
+}
+
+fn list_header(columns: &[BrowseColumn]) -> String {
+    let mut header = format!(
+        "{:<28} {:<15} {:>3} {:>4}",
+        "Hostname", "IP Addr", "Gen", "RSSI"
+    );
+    for &column in columns {
+        header += &format!(" {:<1$}", column_title(column), column_width(column));
+    }
+    header
+}
+
+fn list_row(device: &ShellyDevice, columns: &[BrowseColumn]) -> String {
+    let mut row = format!(
+        "{:<28} {:<15} {:>3} {:>4}",
+        device.hostname, device.ip, device.gen, device.rssi
+    );
+    for &column in columns {
+        let cell = health_cell(device, column).get_content();
+        let cell: String = cell.chars().take(column_width(column)).collect();
+        row += &format!(" {:<1$}", cell, column_width(column));
+    }
+    row
+}
+
+/// Prints `text` at (`x`, `y`), cut or padded to exactly `width` columns.
+fn line(
+    out: &mut impl Write,
+    x: usize,
+    y: usize,
+    width: usize,
+    text: &str,
+    reverse: bool,
+) -> Result<()> {
+    let mut text: String = text.chars().take(width).collect();
+    let len = text.chars().count();
+    text.extend(std::iter::repeat_n(' ', width - len));
+
+    queue!(out, MoveTo(x as u16, y as u16))?;
+    if reverse {
+        queue!(
+            out,
+            SetAttribute(Attribute::Reverse),
+            Print(text),
+            SetAttribute(Attribute::Reset)
+        )?;
+    } else {
+        queue!(out, Print(text))?;
+    }
+    if x + width >= terminal::size()?.0 as usize {
+        queue!(out, Clear(ClearType::UntilNewLine))?;
+    }
+    Ok(())
+}
+
+/// Content of a side pane, one entry per line, and the outputs of a status.
+async fn pane_content(
+    client: &Client,
+    ip: &str,
+    gen: u32,
+    pane: Pane,
+) -> Result<(Vec<String>, Option<Vec<Output>>)> {
+    let value = match (pane, gen) {
+        (Pane::Status, 1) => get_gen1(client, ip, "status").await?,
+        (Pane::Config, 1) => get_gen1(client, ip, "settings").await?,
+        (Pane::Scripts, 1) => return Ok((vec!["Scripts need a Gen2+ device".to_string()], None)),
+        (Pane::Status, _) => call(client, ip, "Shelly.GetStatus", json!({})).await?,
+        (Pane::Config, _) => fetch_config(client, ip).await?,
+        (Pane::Scripts, _) => {
+            let list = call(client, ip, "Script.List", json!({})).await?;
+            return Ok((script_lines(&list), None));
+        }
+    };
+    let outputs = (pane == Pane::Status).then(|| outputs(&value, gen));
+    let lines = serde_json::to_string_pretty(&value)?
+        .lines()
+        .map(String::from)
+        .collect();
+    Ok((lines, outputs))
+}
+
+fn script_lines(list: &Value) -> Vec<String> {
+    let scripts = list["scripts"].as_array().cloned().unwrap_or_default();
+    if scripts.is_empty() {
+        return vec!["No scripts".to_string()];
+    }
+    scripts
+        .iter()
+        .map(|s| {
+            format!(
+                "{:>3}  {:<24} {:<8} {}",
+                s["id"],
+                s["name"].as_str().unwrap_or("<unknown>"),
+                if s["running"].as_bool().unwrap_or(false) {
+                    "running"
+                } else {
+                    "stopped"
+                },
+                if s["enable"].as_bool().unwrap_or(false) {
+                    "autostart"
+                } else {
+                    ""
+                }
+            )
+        })
+        .collect()
+}
+
+/// Switches and lights in a status, e.g. `switch:0` and `switch:1` on a 2PM.
+///
+/// Gen1 devices list theirs in `relays` and `lights` arrays instead.
+fn outputs(status: &Value, gen: u32) -> Vec<Output> {
+    let mut outputs = Vec::new();
+    if gen == 1 {
+        for (kind, key) in [("relay", "relays"), ("light", "lights")] {
+            let count = status[key].as_array().map_or(0, Vec::len);
+            outputs.extend((0..count as u64).map(|id| Output { kind, id }));
+        }
+        return outputs;
+    }
+    for key in status.as_object().into_iter().flat_map(|o| o.keys()) {
+        let Some((kind, id)) = key.split_once(':') else {
+            continue;
+        };
+        let kind = match kind {
+            "switch" => "switch",
+            "light" => "light",
+            _ => continue,
+        };
+        if let Ok(id) = id.parse() {
+            outputs.push(Output { kind, id });
+        }
+    }
+    outputs
+}
+
+async fn get_gen1(client: &Client, ip: &str, path: &str) -> Result<Value> {
+    let resp = client.get(device_url(ip, path)).send().await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("/{} failed: {}", path, resp.status()));
+    }
+    Ok(resp.json().await?)
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Toggle(Output),
+    Reboot,
+}
+
+/// Runs `action` on `device` in the background, reporting back a message.
+fn spawn_action(
+    client: &Client,
+    device: &ShellyDevice,
+    action: Action,
+    tx: &mpsc::UnboundedSender<Update>,
+) {
+    let (client, tx) = (client.clone(), tx.clone());
+    let (hostname, ip, gen) = (device.hostname.clone(), device.ip.clone(), device.gen);
+    tokio::spawn(async move {
+        let message = match run_action(&client, &ip, gen, action).await {
+            Ok(done) => format!("✅ {}: {}", hostname, done),
+            Err(e) => format!("❌ {}: {}", hostname, e),
+        };
+        let _ = tx.send(Update::Message(message));
+    });
+}
+
+async fn run_action(client: &Client, ip: &str, gen: u32, action: Action) -> Result<String> {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    match (action, gen) {
+        (Action::Toggle(output), 1) => {
+            let path = format!("{}/{}?turn=toggle", output.kind, output.id);
+            let resp = get_gen1(client, ip, &path).await?;
+            let on = resp["ison"].as_bool().unwrap_or(false);
+            Ok(format!("{} turned {}", output, on_off(on)))
+        }
+        (Action::Toggle(output), _) => {
+            let method = match output.kind {
+                "light" => "Light.Toggle",
+                _ => "Switch.Toggle",
+            };
+            let resp = call(client, ip, method, json!({ "id": output.id })).await?;
+            let was_on = resp["was_on"].as_bool().unwrap_or(false);
+            Ok(format!("{} turned {}", output, on_off(!was_on)))
+        }
+        (Action::Reboot, 1) => {
+            get_gen1(client, ip, "reboot").await?;
+            Ok("rebooting".to_string())
+        }
+        (Action::Reboot, _) => {
+            call(client, ip, "Shelly.Reboot", json!({})).await?;
+            Ok("rebooting".to_string())
+        }
+    }
+}
+
+/// Opens `url` in the default browser.
+fn open_url(url: &str) -> std::io::Result<()> {
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else if cfg!(windows) {
+        ("cmd", &["/C", "start", ""])
+    } else {
+        ("xdg-open", &[])
+    };
+    Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
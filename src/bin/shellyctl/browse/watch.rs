@@ -0,0 +1,226 @@
+use super::{
+    advertised, browse_services, cache_entry, daemon, filter, probe, render_table, scan, Repaint,
+    Search, ShellyDevice, MAX_PROBES, SHELLY_SERVICE,
+};
+use crate::cli::{BrowseColumn, BrowseOutput, BrowseSort};
+use crate::resolve;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use mdns_sd::ServiceEvent;
+use prettytable::Cell;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{stdout, IsTerminal};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::{interval, MissedTickBehavior};
+
+#[derive(Clone, Serialize)]
+struct Watched {
+    #[serde(flatten)]
+    device: ShellyDevice,
+    online: bool,
+    last_seen: DateTime<Local>,
+}
+
+/// Outcome of probing the host that answered to `hostname` in mDNS.
+type ProbeResult = (String, Option<ShellyDevice>);
+
+/// Browses until Ctrl-C, re-probing known devices every `search.timeout`.
+///
+/// Devices go offline when mDNS reports them removed or a re-probe fails, and
+/// come back online with the next successful probe. A device that stops
+/// matching the filter, e.g. when its signal recovers, leaves the table.
+pub(super) async fn watch(search: &Search<'_>, output: BrowseOutput) -> Result<()> {
+    if !matches!(output, BrowseOutput::Table | BrowseOutput::Ndjson) {
+        bail!("--watch only supports table and ndjson output");
+    }
+
+    let mdns = daemon(search.interface)?;
+    let mut events = browse_services(&mdns)?;
+
+    let client = Client::new();
+    let limit = Arc::new(Semaphore::new(MAX_PROBES));
+    let (tx, mut rx) = mpsc::unbounded_channel::<ProbeResult>();
+    let spawn_probe = |seed: ShellyDevice| {
+        let (client, limit, tx) = (client.clone(), limit.clone(), tx.clone());
+        tokio::spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let hostname = seed.hostname.clone();
+            let device = if seed.gen == 0 {
+                scan::probe_any(&client, seed).await
+            } else {
+                probe(&client, seed).await
+            };
+            let _ = tx.send((hostname, device));
+        });
+    };
+
+    let mut devices = BTreeMap::<String, Watched>::new();
+    // What mDNS advertised for each host, re-probed on every refresh
+    let mut seeds = HashMap::<String, ShellyDevice>::new();
+    let mut fullnames = HashMap::<String, String>::new();
+    // Hosts with a probe in flight, so a slow device never has two at once
+    let mut pending = HashSet::<String>::new();
+
+    let mut view = View::new(output, search.sort, search.columns);
+    view.draw(&devices, false, None)?;
+
+    let mut ticker = interval(search.timeout);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    ticker.tick().await;
+
+    loop {
+        let changed = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = ticker.tick() => {
+                for seed in seeds.values() {
+                    if pending.insert(seed.hostname.clone()) {
+                        spawn_probe(seed.clone());
+                    }
+                }
+                None
+            }
+            Some((hostname, device)) = rx.recv() => {
+                pending.remove(&hostname);
+                match device {
+                    Some(device) if !search.filter.matches(&device) => {
+                        match devices.remove(&hostname) {
+                            Some(_) => Some((hostname, true)),
+                            None => continue,
+                        }
+                    }
+                    Some(device) => {
+                        // Routine re-probes only matter to ndjson when something moved
+                        let notable = devices.get(&hostname).is_none_or(|w| {
+                            !w.online || w.device.rssi != device.rssi || w.device.ip != device.ip
+                        });
+                        if notable {
+                            resolve::remember([cache_entry(&device)]);
+                        }
+                        let last_seen = Local::now();
+                        devices.insert(hostname.clone(), Watched { device, online: true, last_seen });
+                        Some((hostname, notable))
+                    }
+                    None => mark_offline(&mut devices, &hostname).map(|h| (h, true)),
+                }
+            }
+            event = events.recv() => match event {
+                Some(ServiceEvent::ServiceResolved(info)) => {
+                    let Some(seed) = advertised(&info) else {
+                        continue;
+                    };
+                    let hostname = seed.hostname.clone();
+                    fullnames.insert(info.get_fullname().to_string(), hostname.clone());
+
+                    // TXT records from `_shelly` win over a plain `_http` advert
+                    let from_txt = seeds.get(&hostname).is_some_and(|s| s.gen > 0);
+                    if info.get_type() == SHELLY_SERVICE || !from_txt {
+                        seeds.insert(hostname.clone(), seed);
+                    }
+
+                    // New hosts, and known ones coming back, are probed right away
+                    let online = devices.get(&hostname).is_some_and(|w| w.online);
+                    if !online && pending.insert(hostname.clone()) {
+                        spawn_probe(seeds[&hostname].clone());
+                    }
+                    None
+                }
+                Some(ServiceEvent::ServiceRemoved(_, fullname)) => match fullnames.get(&fullname) {
+                    Some(hostname) => {
+                        mark_offline(&mut devices, &hostname.clone()).map(|h| (h, true))
+                    }
+                    None => None,
+                },
+                Some(_) => None,
+                None => break,
+            },
+        };
+
+        if let Some((hostname, notable)) = changed {
+            let changed = devices.get(&hostname).filter(|_| notable);
+            view.draw(&devices, notable, changed)?;
+        }
+    }
+
+    let _ = mdns.shutdown();
+    Ok(())
+}
+
+/// Returns the hostname if the device was online until now.
+fn mark_offline(devices: &mut BTreeMap<String, Watched>, hostname: &str) -> Option<String> {
+    let watched = devices.get_mut(hostname).filter(|w| w.online)?;
+    watched.online = false;
+    Some(hostname.to_string())
+}
+
+/// Live table on a terminal, otherwise a reprinted table or one NDJSON line per change.
+struct View<'a> {
+    output: BrowseOutput,
+    sort: BrowseSort,
+    columns: &'a [BrowseColumn],
+    repaint: Option<Repaint>,
+}
+
+impl<'a> View<'a> {
+    fn new(output: BrowseOutput, sort: BrowseSort, columns: &'a [BrowseColumn]) -> Self {
+        let repaint = stdout().is_terminal().then(Repaint::default);
+        if output == BrowseOutput::Table {
+            println!("🔍 Watching for Shelly devices on the network (Ctrl-C to stop)...\n");
+        }
+        View {
+            output,
+            sort,
+            columns,
+            repaint,
+        }
+    }
+
+    /// Redraws the table; ndjson only prints `changed`, if any.
+    ///
+    /// Off a terminal the table is only reprinted when something `notable`
+    /// happened, not after every routine re-probe.
+    fn draw(
+        &mut self,
+        devices: &BTreeMap<String, Watched>,
+        notable: bool,
+        changed: Option<&Watched>,
+    ) -> Result<()> {
+        if self.output == BrowseOutput::Ndjson {
+            if let Some(watched) = changed {
+                println!("{}", serde_json::to_string(watched)?);
+            }
+            return Ok(());
+        }
+        if self.repaint.is_none() && !notable {
+            return Ok(());
+        }
+
+        let mut watched: Vec<&Watched> = devices.values().collect();
+        filter::sort_by(&mut watched, self.sort, |w| &w.device);
+        let plain: Vec<&ShellyDevice> = watched.iter().map(|w| &w.device).collect();
+        let mut table = render_table(&plain, self.columns);
+
+        let header = table.get_mut_row(0).expect("table has a header row");
+        header.add_cell(Cell::new("Status").style_spec("Fc"));
+        header.add_cell(Cell::new("Last Seen").style_spec("Fc"));
+        for (i, watched) in watched.into_iter().enumerate() {
+            let row = table.get_mut_row(i + 1).expect("one row per device");
+            row.add_cell(if watched.online {
+                Cell::new("online").style_spec("Fg")
+            } else {
+                Cell::new("offline").style_spec("Fr")
+            });
+            row.add_cell(Cell::new(&watched.last_seen.format("%H:%M:%S").to_string()));
+        }
+
+        match &mut self.repaint {
+            Some(repaint) => repaint.draw(&table),
+            None => {
+                table.printstd();
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+pub const DEFAULT_CATALOG_MANIFEST: &str =
+    "https://raw.githubusercontent.com/ALLTERCO/shelly-script-examples/main/examples-manifest.json";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -26,6 +29,10 @@ pub enum Commands {
     },
 
     Browse(BrowseArgs),
+
+    Backup(BackupArgs),
+
+    Restore(RestoreArgs),
 }
 
 #[derive(Subcommand)]
@@ -38,6 +45,25 @@ pub enum ScriptCommand {
 
     #[command(visible_alias = "ls")]
     List(ListScriptsArgs),
+
+    #[command(visible_alias = "inv")]
+    Inventory(ScriptInventoryArgs),
+
+    #[command(visible_alias = "cat")]
+    Catalog {
+        #[command(subcommand)]
+        command: CatalogCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CatalogCommand {
+    #[command(visible_alias = "ls")]
+    List(CatalogListArgs),
+
+    Show(CatalogShowArgs),
+
+    Install(CatalogInstallArgs),
 }
 
 #[derive(Subcommand)]
@@ -47,6 +73,27 @@ pub enum ConfigCommand {
 
     #[command(name = "dump")]
     Dump(ConfigDumpArgs),
+
+    #[command(name = "get")]
+    Get(ConfigGetArgs),
+
+    #[command(name = "apply")]
+    Apply(ConfigApplyArgs),
+
+    #[command(name = "diff")]
+    Diff(ConfigDiffArgs),
+
+    #[command(name = "watch")]
+    Watch(ConfigWatchArgs),
+
+    #[command(name = "edit")]
+    Edit(ConfigEditArgs),
+
+    #[command(name = "history")]
+    History(ConfigHistoryArgs),
+
+    #[command(name = "rollback")]
+    Rollback(ConfigRollbackArgs),
 }
 
 #[derive(Args)]
@@ -93,10 +140,231 @@ pub struct ListScriptsArgs {
     pub device: String,
 }
 
+#[derive(Args)]
+pub struct ScriptInventoryArgs {
+    /// Device IP or hostname (repeatable); devices are discovered via mDNS when omitted
+    #[arg(short, long = "device", value_name = "DEVICE")]
+    pub devices: Vec<String>,
+
+    /// Inventory file with [[devices]] entries (name, address), e.g. shellymon.toml
+    #[arg(short, long)]
+    pub inventory: Option<String>,
+
+    /// Directory with reference scripts (<name>.js) to compare device copies against
+    #[arg(short, long)]
+    pub reference: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CatalogListArgs {
+    /// Script manifest (local path or URL)
+    #[arg(short, long, default_value = DEFAULT_CATALOG_MANIFEST)]
+    pub manifest: String,
+}
+
+#[derive(Args)]
+pub struct CatalogShowArgs {
+    /// Script ID from the manifest
+    pub id: String,
+
+    /// Script manifest (local path or URL)
+    #[arg(short, long, default_value = DEFAULT_CATALOG_MANIFEST)]
+    pub manifest: String,
+
+    /// Print the full script code
+    #[arg(long)]
+    pub code: bool,
+}
+
+#[derive(Args)]
+pub struct CatalogInstallArgs {
+    /// Script ID from the manifest
+    pub id: String,
+
+    #[arg(short, long, help = "Device IP or hostname", alias = "d")]
+    pub device: String,
+
+    /// Script manifest (local path or URL)
+    #[arg(short, long, default_value = DEFAULT_CATALOG_MANIFEST)]
+    pub manifest: String,
+
+    /// Script name on the device (defaults to the script ID)
+    #[arg(short, long)]
+    pub name: Option<String>,
+
+    /// Override a CONFIG entry of the script, e.g. --set debug=true or --set 'ids:=[1,2]'
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    pub set: Vec<String>,
+
+    /// Force overwrite the script even if one exists or is running
+    #[arg(long)]
+    pub force: bool,
+
+    /// Enable script after upload
+    #[arg(short, long)]
+    pub enable: bool,
+}
+
 #[derive(Args)]
 pub struct BrowseArgs {
     #[arg(long, help = "Filter by device type (comma-separated)")]
     pub r#type: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value_t = BrowseOutput::Table,
+        help = "Output format; ndjson prints each device as soon as it is found"
+    )]
+    pub output: BrowseOutput,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Discovery window in seconds; with --watch, the re-probe interval"
+    )]
+    pub timeout: u64,
+
+    #[arg(
+        short,
+        long,
+        help = "Keep running and track devices going online and offline (table or ndjson)"
+    )]
+    pub watch: bool,
+
+    #[arg(
+        long,
+        value_name = "CIDR",
+        conflicts_with = "watch",
+        help = "Probe every address of an IPv4 subnet instead of using mDNS, e.g. 10.0.20.0/24"
+    )]
+    pub scan: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        value_name = "NAME|ADDR",
+        conflicts_with = "scan",
+        help = "Only browse on this network interface, given by name (eth0) or address"
+    )]
+    pub interface: Option<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Only show these generations (comma-separated)"
+    )]
+    pub gen: Vec<u32>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Only show these apps, e.g. Plus1PM (comma-separated)"
+    )]
+    pub app: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "REQ",
+        help = "Only show firmware matching a version requirement, e.g. '<1.4.0'"
+    )]
+    pub fw: Option<String>,
+
+    #[arg(long, help = "Only show devices connected to this SSID")]
+    pub ssid: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        help = "Only show devices whose name matches"
+    )]
+    pub name: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DBM",
+        allow_negative_numbers = true,
+        help = "Only show devices with a weaker signal, e.g. -70"
+    )]
+    pub rssi_below: Option<i32>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = BrowseSort::Hostname,
+        help = "Row order; rssi and fw put weak and outdated devices first"
+    )]
+    pub sort: BrowseSort,
+
+    #[arg(
+        short,
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "Extra health columns for the table (comma-separated)"
+    )]
+    pub columns: Vec<BrowseColumn>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum BrowseOutput {
+    /// Live-updating table
+    Table,
+    /// JSON array once discovery completes
+    Json,
+    /// CSV with a header row once discovery completes
+    Csv,
+    /// One JSON object per line, streamed as devices are found
+    Ndjson,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum BrowseColumn {
+    /// Time since boot
+    Uptime,
+    /// Internal temperature
+    Temp,
+    /// Firmware update on offer
+    Update,
+    /// Connected to Shelly Cloud
+    Cloud,
+    /// Connected to the MQTT broker
+    Mqtt,
+    /// Restart needed to apply config
+    Restart,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum BrowseSort {
+    Hostname,
+    Rssi,
+    Ip,
+    Fw,
+}
+
+#[derive(Args)]
+pub struct BackupArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Archive to write (tar)
+    #[arg(short, long)]
+    pub output: String,
+}
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Archive created by `backup`
+    #[arg(short, long)]
+    pub input: String,
 }
 
 #[derive(Args)]
@@ -106,8 +374,21 @@ pub struct ConfigSetArgs {
     pub device: String,
 
     /// Key-value pairs to modify, e.g. Sys.device.name=klima
+    ///
+    /// Plain values are typed automatically; quote them to keep a string
+    /// (wifi.sta.ssid='"1234"'). Use key:=<json> for raw JSON
+    /// (wifi.sta.ip:=null), key=@file to read a string from a file and
+    /// key:=@file.json to read JSON from a file.
     #[arg(required = true)]
     pub pairs: Vec<String>,
+
+    /// Validate and print the SetConfig requests without sending them
+    #[arg(long, conflicts_with = "reboot")]
+    pub dry_run: bool,
+
+    /// Reboot the device if a change requires it, then verify the new values
+    #[arg(long)]
+    pub reboot: bool,
 }
 
 #[derive(Args)]
@@ -119,4 +400,131 @@ pub struct ConfigDumpArgs {
     /// Optional key path to print a subtree, e.g. .wifi.ap
     #[arg(long)]
     pub subtree: Option<String>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = DumpFormat::Tree)]
+    pub format: DumpFormat,
+}
+
+#[derive(Args)]
+pub struct ConfigGetArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Key paths to print, e.g. sys.device.name or switch:0.name; exits with 2 if any is missing
+    #[arg(required = true)]
+    pub keys: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ConfigApplyArgs {
+    /// Device IP or hostname
+    #[arg(short, long, required_unless_present = "profile")]
+    pub device: Option<String>,
+
+    /// Desired config (TOML, YAML or JSON) laid out like Shelly.GetConfig
+    #[arg(short, long, required_unless_present = "profile")]
+    pub file: Option<String>,
+
+    /// Fleet profile layering base, group and per-device settings
+    #[arg(short, long, conflicts_with_all = ["device", "file"])]
+    pub profile: Option<String>,
+
+    /// Profile members to apply: @group or device addresses (default: all)
+    #[arg(requires = "profile")]
+    pub targets: Vec<String>,
+
+    /// Only print the plan; exit with 2 if any device differs
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Args)]
+pub struct ConfigDiffArgs {
+    /// Device IP/hostname or saved config snapshot (JSON, YAML or TOML)
+    pub left: String,
+
+    /// Device IP/hostname or saved config snapshot to compare against
+    pub right: String,
+
+    /// Key pattern to ignore, `*` matches anything, e.g. --ignore 'sys.device.*' (repeatable)
+    #[arg(short, long, value_name = "PATTERN")]
+    pub ignore: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ConfigWatchArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Seconds between cfg_rev polls
+    #[arg(short, long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+
+    /// Key pattern to leave out of the reported diffs (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub ignore: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ConfigEditArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Optional key path to edit only a subtree, e.g. .wifi.ap
+    #[arg(long)]
+    pub subtree: Option<String>,
+
+    /// Edit as JSON instead of YAML
+    #[arg(long)]
+    pub json: bool,
+
+    /// Apply without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args)]
+pub struct ConfigHistoryArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Number of most recent changes to list
+    #[arg(short = 'n', long, default_value_t = 20)]
+    pub limit: u32,
+
+    /// Print the full diff of one change
+    #[arg(long, value_name = "ID")]
+    pub show: Option<i64>,
+}
+
+#[derive(Args)]
+pub struct ConfigRollbackArgs {
+    /// Device IP or hostname
+    #[arg(short, long)]
+    pub device: String,
+
+    /// Undo change ID and everything after it, restoring the config from before it
+    #[arg(long, value_name = "ID")]
+    pub to: i64,
+
+    /// Apply without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DumpFormat {
+    /// Colored indented tree
+    Tree,
+    Json,
+    Yaml,
+    /// TOML (null values are omitted)
+    Toml,
+    /// One `key.path=value` line per leaf, accepted by `config set`
+    Flat,
 }
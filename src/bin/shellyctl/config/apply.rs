@@ -0,0 +1,256 @@
+use crate::cli::ConfigApplyArgs;
+use crate::config::dump::fetch_config;
+use crate::config::history;
+use crate::config::profile::Profile;
+use crate::config::set::{
+    cfg_rev, find_method, list_methods, restart_required, set_config, split_component,
+};
+use crate::config::tree::{
+    diff, insert_path, is_ignored, load_tree, print_changes, Change, Difference, READ_ONLY_KEYS,
+    WRITE_ONLY_KEYS,
+};
+use crate::resolve::resolve_all;
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Exit code of `--check` when the device does not match the desired state.
+const EXIT_DRIFT: i32 = 2;
+
+/// How often to re-plan when the device config changes underneath us.
+const REPLAN_ATTEMPTS: usize = 3;
+
+/// Result of bringing one device towards its desired config.
+pub enum Outcome {
+    InSync,
+    Drift(usize),
+    Applied(usize),
+}
+
+pub async fn handle(args: ConfigApplyArgs) -> Result<()> {
+    let client = Client::new();
+
+    if let Some(profile) = &args.profile {
+        return apply_profile(&client, profile, &args.targets, args.check).await;
+    }
+
+    let (Some(device), Some(file)) = (&args.device, &args.file) else {
+        bail!("--device and --file are required without --profile");
+    };
+
+    let desired = load_tree(file)?;
+    if !desired.is_object() {
+        bail!("{}: desired config must be a table of components", file);
+    }
+
+    if let Outcome::Drift(_) = converge(&client, device, &desired, file, args.check).await? {
+        std::process::exit(EXIT_DRIFT);
+    }
+    Ok(())
+}
+
+/// Plans `desired` against `device` and applies it unless `check` is set.
+pub async fn converge(
+    client: &Client,
+    device: &str,
+    desired: &Value,
+    source: &str,
+    check: bool,
+) -> Result<Outcome> {
+    for attempt in 0..REPLAN_ATTEMPTS {
+        let rev = cfg_rev(client, device).await?;
+        let current = fetch_config(client, device).await?;
+        if attempt == 0 {
+            warn_write_only(&current, desired);
+        }
+        let changes = plan(&current, desired);
+
+        if changes.is_empty() {
+            println!("✅ {} matches {}", device, source);
+            return Ok(Outcome::InSync);
+        }
+
+        print_plan(device, &changes);
+
+        if check {
+            return Ok(Outcome::Drift(changes.len()));
+        }
+
+        // Someone else changed the device since it was read, plan again
+        let current_rev = cfg_rev(client, device).await?;
+        if current_rev != rev {
+            eprintln!(
+                "⚠️  Config of {} changed while planning (cfg_rev {} → {}), re-planning",
+                device, rev, current_rev
+            );
+            continue;
+        }
+
+        apply_changes(client, device, &changes).await?;
+        return Ok(Outcome::Applied(changes.len()));
+    }
+
+    bail!(
+        "Config of {} kept changing, gave up after {} attempts",
+        device,
+        REPLAN_ATTEMPTS
+    )
+}
+
+/// Renders a profile for each targeted device and converges them one by one.
+async fn apply_profile(client: &Client, file: &str, targets: &[String], check: bool) -> Result<()> {
+    let profile = Profile::load(file)?;
+    let devices = profile.members(targets)?;
+    if devices.is_empty() {
+        bail!("{}: no devices selected", file);
+    }
+    let mut addresses = devices.clone();
+    resolve_all(addresses.iter_mut().collect()).await;
+
+    let mut results = Vec::new();
+    for (device, address) in devices.iter().zip(&addresses) {
+        let desired = profile.render(device);
+        let source = format!("{} ({})", file, device);
+        results.push((
+            device,
+            converge(client, address, &desired, &source, check).await,
+        ));
+        println!();
+    }
+
+    let mut drifted = 0;
+    let mut failed = 0;
+    println!("Summary:");
+    for (device, result) in results {
+        match result {
+            Ok(Outcome::InSync) => println!("  ✅ {}: in sync", device),
+            Ok(Outcome::Applied(n)) => println!("  ✅ {}: {} change(s) applied", device, n),
+            Ok(Outcome::Drift(n)) => {
+                println!("  ⚠️  {}: drifted, {} change(s) pending", device, n);
+                drifted += 1;
+            }
+            Err(err) => {
+                println!("  ❌ {}: {}", device, err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} device(s) failed", failed, devices.len());
+    }
+    if drifted > 0 {
+        std::process::exit(EXIT_DRIFT);
+    }
+    Ok(())
+}
+
+/// Keys of `desired` whose value differs from `current`; keys only on the device are kept.
+///
+/// Read-only keys cannot be set and write-only keys cannot be compared, so both are left out.
+pub fn plan(current: &Value, desired: &Value) -> Vec<Difference> {
+    diff(current, desired)
+        .into_iter()
+        .filter(|d| !matches!(d.change, Change::Removed(_)))
+        .filter(|d| !is_ignored(&d.key(), READ_ONLY_KEYS))
+        .filter(|d| !is_ignored(&d.key(), WRITE_ONLY_KEYS))
+        .collect()
+}
+
+/// Warns about write-only keys in `desired`, which `plan` leaves out.
+pub fn warn_write_only(current: &Value, desired: &Value) {
+    let keys: Vec<String> = diff(current, desired)
+        .iter()
+        .filter(|d| !matches!(d.change, Change::Removed(_)))
+        .map(|d| d.key())
+        .filter(|k| is_ignored(k, WRITE_ONLY_KEYS))
+        .collect();
+    if !keys.is_empty() {
+        eprintln!(
+            "⚠️  Write-only keys cannot be compared and are not applied, use config set: {}",
+            keys.join(", ")
+        );
+    }
+}
+
+pub fn print_plan(device: &str, changes: &[Difference]) {
+    let components: BTreeSet<&str> = changes
+        .iter()
+        .filter_map(|d| d.path.first().map(String::as_str))
+        .collect();
+
+    println!("Plan for {}:\n", device);
+    print_changes(changes);
+    println!(
+        "\nPlan: {} change(s) in {} component(s).",
+        changes.len(),
+        components.len()
+    );
+}
+
+/// Issues one `<Component>.SetConfig` call per component touched by `changes`.
+pub async fn apply_changes(client: &Client, device: &str, changes: &[Difference]) -> Result<()> {
+    let methods = list_methods(client, device).await?;
+    let before = fetch_config(client, device).await?;
+
+    let mut failed = 0;
+    let mut by_component = BTreeMap::<String, Map<String, Value>>::new();
+    for d in changes {
+        let value = match &d.change {
+            Change::Added(v) | Change::Changed(_, v) => v.clone(),
+            Change::Removed(_) => continue,
+        };
+        match d.path.split_first() {
+            Some((component, rest)) if !rest.is_empty() => insert_path(
+                by_component.entry(component.clone()).or_default(),
+                rest,
+                value,
+            ),
+            // A whole component the device lacks, e.g. switch:1 on a 1PM
+            _ => {
+                eprintln!("❌ Component not supported: {} on {}", d.key(), device);
+                failed += 1;
+            }
+        }
+    }
+
+    let mut restart = false;
+    for (key, config) in by_component {
+        let (component, id) = split_component(&key)?;
+
+        let Some(set_method) = find_method(&methods, &component, "setconfig") else {
+            eprintln!("❌ Method not supported: {}.SetConfig", component);
+            failed += 1;
+            continue;
+        };
+
+        match set_config(client, device, &set_method, id, config).await {
+            Ok(resp) if restart_required(&resp) => {
+                println!(
+                    "✅ {} updated {} on {} (restart required)",
+                    set_method, key, device
+                );
+                restart = true;
+            }
+            Ok(_) => println!("✅ {} updated {} on {}", set_method, key, device),
+            Err(err) => {
+                eprintln!("❌ Failed to update {}: {}", key, err);
+                failed += 1;
+            }
+        }
+    }
+
+    history::record(client, device, &before).await;
+
+    if restart {
+        eprintln!(
+            "⚠️  {} must be restarted for the changes to take effect",
+            device
+        );
+    }
+    if failed > 0 {
+        bail!("{} component(s) failed to update", failed);
+    }
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use crate::cli::ConfigDiffArgs;
+use crate::config::dump::fetch_config;
+use crate::config::tree::{diff, is_ignored, load_tree, print_changes, Change};
+use anyhow::Result;
+use colored::*;
+use reqwest::Client;
+use serde_json::Value;
+use std::path::Path;
+
+/// Exit codes as with diff(1): differences are 1, so failures must not be.
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_TROUBLE: i32 = 2;
+
+pub async fn handle(args: ConfigDiffArgs) -> Result<()> {
+    if let Err(e) = run(args).await {
+        eprintln!("❌ {:#}", e);
+        std::process::exit(EXIT_TROUBLE);
+    }
+    Ok(())
+}
+
+async fn run(args: ConfigDiffArgs) -> Result<()> {
+    let client = Client::new();
+    let left = load(&client, &args.left).await?;
+    let right = load(&client, &args.right).await?;
+
+    let changes: Vec<_> = diff(&left, &right)
+        .into_iter()
+        .filter(|d| !is_ignored(&d.key(), &args.ignore))
+        .collect();
+
+    if changes.is_empty() {
+        println!("✅ {} and {} are identical", args.left, args.right);
+        return Ok(());
+    }
+
+    println!("{} {}", "---".red(), args.left);
+    println!("{} {}\n", "+++".green(), args.right);
+    print_changes(&changes);
+
+    let count = |f: fn(&Change) -> bool| changes.iter().filter(|d| f(&d.change)).count();
+    println!(
+        "\n{} added, {} removed, {} changed",
+        count(|c| matches!(c, Change::Added(_))),
+        count(|c| matches!(c, Change::Removed(_))),
+        count(|c| matches!(c, Change::Changed(..)))
+    );
+
+    std::process::exit(EXIT_DIFFERENT);
+}
+
+/// Reads a snapshot file if `source` names one, otherwise queries it as a device.
+async fn load(client: &Client, source: &str) -> Result<Value> {
+    if Path::new(source).is_file() {
+        load_tree(source)
+    } else {
+        fetch_config(client, source).await
+    }
+}
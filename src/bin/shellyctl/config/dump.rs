@@ -1,12 +1,48 @@
-use crate::cli::ConfigDumpArgs;
+use crate::cli::{ConfigDumpArgs, DumpFormat};
+use crate::config::set::parse_value;
+use crate::path::{escape_key, Path};
+use crate::rpc::rpc_url;
 use anyhow::{bail, Result};
 use colored::*;
 use reqwest::Client;
 use serde_json::Value;
+use std::io::IsTerminal;
 
 pub async fn handle(args: ConfigDumpArgs) -> Result<()> {
-    let url = format!("http://{}/rpc/Shelly.GetConfig", args.device);
     let client = Client::new();
+    let config = fetch_config(&client, &args.device).await?;
+
+    let subtree = args.subtree.as_deref().map(Path::parse).transpose()?;
+    let data = match &subtree {
+        Some(path) => path.get(&config)?.clone(),
+        None => config,
+    };
+
+    if !std::io::stdout().is_terminal() {
+        colored::control::set_override(false);
+    }
+
+    match args.format {
+        DumpFormat::Tree => print_tree(&data, 0),
+        DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&data)?),
+        DumpFormat::Yaml => print!("{}", serde_norway::to_string(&data)?),
+        DumpFormat::Toml => {
+            if !data.is_object() {
+                bail!("TOML output needs a table; pick a broader --subtree or another format");
+            }
+            print!("{}", toml::to_string_pretty(&strip_nulls(data))?);
+        }
+        DumpFormat::Flat => {
+            let prefix = subtree.map(|p| p.to_string()).unwrap_or_default();
+            print_flat(&data, &prefix);
+        }
+    }
+    Ok(())
+}
+
+/// Fetches the complete device configuration with Shelly.GetConfig.
+pub async fn fetch_config(client: &Client, device: &str) -> Result<Value> {
+    let url = rpc_url(device, "Shelly.GetConfig");
 
     let resp = client
         .post(&url)
@@ -14,20 +50,53 @@ pub async fn handle(args: ConfigDumpArgs) -> Result<()> {
         .send()
         .await?;
 
-    let mut data: Value = resp.json().await?;
+    Ok(resp.json().await?)
+}
+
+/// Prints one `path=value` line per leaf, in the syntax `config set` accepts.
+fn print_flat(value: &Value, path: &str) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
 
-    if let Some(subtree_path) = &args.subtree {
-        let path = subtree_path.trim_start_matches('.').split('.');
-        for key in path {
-            match data.get(key) {
-                Some(sub) => data = sub.clone(),
-                None => bail!("Subtree path '{}' not found", subtree_path),
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                print_flat(v, &join(&escape_key(k)));
             }
         }
+        // Arrays, null and strings that would be read back differently need key:=<json>
+        Value::String(s)
+            if parse_value(s) == *value && !s.starts_with('@') && !s.contains('\n') =>
+        {
+            println!("{}={}", path, s)
+        }
+        Value::Bool(_) | Value::Number(_) => println!("{}={}", path, value),
+        _ => println!("{}:={}", path, value),
     }
+}
 
-    print_tree(&data, 0);
-    Ok(())
+/// TOML has no null, so drop null members and elements before serializing.
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(arr) => Value::Array(
+            arr.into_iter()
+                .filter(|v| !v.is_null())
+                .map(strip_nulls)
+                .collect(),
+        ),
+        other => other,
+    }
 }
 
 fn print_tree(value: &Value, indent: usize) {
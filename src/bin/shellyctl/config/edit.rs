@@ -0,0 +1,139 @@
+use crate::cli::ConfigEditArgs;
+use crate::config::apply::{apply_changes, plan, print_plan, warn_write_only};
+use crate::config::dump::fetch_config;
+use crate::config::set::cfg_rev;
+use crate::config::tree::{diff, Change};
+use crate::path::Path;
+use anyhow::{anyhow, bail, Result};
+use reqwest::Client;
+use serde_json::Value;
+use std::fs;
+use std::io::{self, Write};
+use std::process::Command;
+
+pub async fn handle(args: ConfigEditArgs) -> Result<()> {
+    let client = Client::new();
+    let device = args.device.as_str();
+
+    let rev = cfg_rev(&client, device).await?;
+    let current = fetch_config(&client, device).await?;
+
+    let path = Path::parse(args.subtree.as_deref().unwrap_or_default())?;
+    let original = path.get(&current)?;
+
+    let ext = if args.json { "json" } else { "yaml" };
+    let name = device.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    let file =
+        std::env::temp_dir().join(format!("shellyctl-{}-{}.{}", name, std::process::id(), ext));
+
+    let text = if args.json {
+        serde_json::to_string_pretty(original)? + "\n"
+    } else {
+        serde_norway::to_string(original)?
+    };
+    fs::write(&file, text)?;
+
+    // Re-open the editor until the file parses or the user gives up
+    let edited = loop {
+        run_editor(&file)?;
+        let text = fs::read_to_string(&file)?;
+        let parsed = if args.json {
+            serde_json::from_str::<Value>(&text).map_err(|e| e.to_string())
+        } else {
+            serde_norway::from_str::<Value>(&text).map_err(|e| e.to_string())
+        };
+        match parsed {
+            Ok(value) => break value,
+            Err(err) => {
+                eprintln!("❌ {}: {}", file.display(), err);
+                if !confirm("Edit again? [Y/n]: ", true)? {
+                    bail!("Edit cancelled, your changes are in {}", file.display());
+                }
+            }
+        }
+    };
+
+    let mut desired = current.clone();
+    path.set(&mut desired, edited, None)?;
+
+    // SetConfig can only set values, so deleted keys would silently stay
+    let removed: Vec<String> = diff(&current, &desired)
+        .iter()
+        .filter(|d| matches!(d.change, Change::Removed(_)))
+        .map(|d| d.key())
+        .collect();
+    if !removed.is_empty() {
+        eprintln!(
+            "⚠️  Deleting keys is not supported, these stay on the device: {}",
+            removed.join(", ")
+        );
+    }
+
+    warn_write_only(&current, &desired);
+    let changes = plan(&current, &desired);
+    if changes.is_empty() {
+        println!("Edit cancelled, no changes made.");
+        let _ = fs::remove_file(&file);
+        return Ok(());
+    }
+
+    print_plan(device, &changes);
+
+    if !args.yes && !confirm("Apply? [y/N]: ", false)? {
+        println!("Cancelled, your changes are in {}", file.display());
+        return Ok(());
+    }
+
+    let current_rev = cfg_rev(&client, device).await?;
+    if current_rev != rev {
+        bail!(
+            "Config of {} changed while editing (cfg_rev {} → {}), nothing was changed; your changes are in {}",
+            device,
+            rev,
+            current_rev,
+            file.display()
+        );
+    }
+
+    apply_changes(&client, device, &changes).await?;
+    let _ = fs::remove_file(&file);
+    Ok(())
+}
+
+/// Opens `file` in $VISUAL or $EDITOR (falling back to vi) and waits for it to exit.
+fn run_editor(file: &std::path::Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Allow editors with arguments, e.g. EDITOR="code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or_else(|| anyhow!("$EDITOR is empty"))?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(file)
+        .status()
+        .map_err(|e| anyhow!("Failed to run {}: {}", program, e))?;
+    if !status.success() {
+        bail!(
+            "{} exited with {}, your changes are in {}",
+            program,
+            status,
+            file.display()
+        );
+    }
+    Ok(())
+}
+
+/// Asks a yes/no question on stdin; an empty answer picks `default`.
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut response = String::new();
+    io::stdin().read_line(&mut response)?;
+    Ok(match response.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => matches!(answer, "y" | "yes"),
+    })
+}
@@ -0,0 +1,31 @@
+use crate::cli::ConfigGetArgs;
+use crate::config::dump::fetch_config;
+use crate::path::Path;
+use anyhow::Result;
+use reqwest::Client;
+use serde_json::Value;
+
+/// Exit code used when at least one requested key does not exist.
+const EXIT_MISSING_KEY: i32 = 2;
+
+pub async fn handle(args: ConfigGetArgs) -> Result<()> {
+    let client = Client::new();
+    let config = fetch_config(&client, &args.device).await?;
+
+    let mut missing = false;
+    for key in &args.keys {
+        match Path::parse(key).and_then(|path| path.get(&config)) {
+            Ok(Value::String(s)) => println!("{}", s),
+            Ok(value) => println!("{}", value),
+            Err(err) => {
+                eprintln!("❌ {}", err);
+                missing = true;
+            }
+        }
+    }
+
+    if missing {
+        std::process::exit(EXIT_MISSING_KEY);
+    }
+    Ok(())
+}
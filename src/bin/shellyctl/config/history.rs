@@ -0,0 +1,266 @@
+use crate::cli::{ConfigHistoryArgs, ConfigRollbackArgs};
+use crate::config::apply::{apply_changes, plan, print_plan};
+use crate::config::dump::fetch_config;
+use crate::config::edit::confirm;
+use crate::config::set::cfg_rev;
+use crate::config::tree::{diff, is_ignored, print_changes, READ_ONLY_KEYS};
+use crate::rpc::call;
+use crate::table::plain_table;
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use log::info;
+use prettytable::{Cell, Row};
+use reqwest::Client;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// How many changed keys to name in the history table before summarizing.
+const SHOWN_KEYS: usize = 3;
+
+struct Entry {
+    id: i64,
+    time: String,
+    /// Device id from Shelly.GetDeviceInfo, which survives address changes
+    device: String,
+    command: String,
+    before: Value,
+    after: Value,
+}
+
+/// Location of the journal, `$XDG_DATA_HOME/shellyctl/history.db` by default.
+fn db_path() -> Result<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("$HOME is not set"))?;
+            PathBuf::from(home).join(".local/share")
+        }
+    };
+    Ok(data_dir.join("shellyctl").join("history.db"))
+}
+
+fn open() -> Result<Connection> {
+    let path = db_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let conn = Connection::open(&path)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS changes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time TEXT NOT NULL,
+            device TEXT NOT NULL,
+            command TEXT NOT NULL,
+            before TEXT NOT NULL,
+            after TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(conn)
+}
+
+fn parse_entry(row: &rusqlite::Row) -> rusqlite::Result<Entry> {
+    let json = |i: usize| -> rusqlite::Result<Value> {
+        let text: String = row.get(i)?;
+        serde_json::from_str(&text).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+        })
+    };
+    Ok(Entry {
+        id: row.get(0)?,
+        time: row.get(1)?,
+        device: row.get(2)?,
+        command: row.get(3)?,
+        before: json(4)?,
+        after: json(5)?,
+    })
+}
+
+/// The id the journal is keyed by, e.g. `shellyplus1pm-a8032ab12345`.
+async fn device_id(client: &Client, device: &str) -> Result<String> {
+    let info = call(client, device, "Shelly.GetDeviceInfo", json!({})).await?;
+    info["id"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("{} did not report a device id", device))
+}
+
+/// The command line with setting values left out, so secrets never reach the journal.
+fn redacted_command() -> String {
+    std::env::args()
+        .skip(1)
+        .map(|arg| match arg.split_once('=') {
+            Some((key, _)) if !arg.starts_with('-') => key.trim_end_matches(':').to_string(),
+            _ => arg,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Journals the change from `before` to the device's current config.
+///
+/// Failing to write the journal never fails the change itself.
+pub async fn record(client: &Client, device: &str, before: &Value) {
+    let result = async {
+        let after = fetch_config(client, device).await?;
+        if changed_keys(before, &after).is_empty() {
+            return Ok(None);
+        }
+
+        let id = device_id(client, device).await?;
+        let conn = open()?;
+        conn.execute(
+            "INSERT INTO changes (time, device, command, before, after) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                id,
+                redacted_command(),
+                before.to_string(),
+                after.to_string()
+            ],
+        )?;
+        anyhow::Ok(Some(conn.last_insert_rowid()))
+    }
+    .await;
+
+    match result {
+        Ok(Some(id)) => info!("Recorded change {} for {}", id, device),
+        Ok(None) => {}
+        Err(err) => eprintln!("⚠️  Failed to record history: {}", err),
+    }
+}
+
+fn changed_keys(before: &Value, after: &Value) -> Vec<String> {
+    diff(before, after)
+        .into_iter()
+        .map(|d| d.key())
+        .filter(|k| !is_ignored(k, READ_ONLY_KEYS))
+        .collect()
+}
+
+pub async fn history(args: ConfigHistoryArgs) -> Result<()> {
+    let id = device_id(&Client::new(), &args.device).await?;
+    let conn = open()?;
+
+    if let Some(show) = args.show {
+        let entry = conn
+            .query_row(
+                "SELECT id, time, device, command, before, after FROM changes WHERE id = ?1 AND device = ?2",
+                params![show, id],
+                parse_entry,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!("No history entry {} for {}", show, args.device))?;
+
+        println!("Change {} on {} at {}", entry.id, entry.device, entry.time);
+        println!("  shellyctl {}\n", entry.command);
+        let changes: Vec<_> = diff(&entry.before, &entry.after)
+            .into_iter()
+            .filter(|d| !is_ignored(&d.key(), READ_ONLY_KEYS))
+            .collect();
+        print_changes(&changes);
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, time, device, command, before, after FROM changes
+         WHERE device = ?1 ORDER BY id DESC LIMIT ?2",
+    )?;
+    let entries = stmt
+        .query_map(params![id, args.limit], parse_entry)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    if entries.is_empty() {
+        println!("No recorded changes for {}", args.device);
+        return Ok(());
+    }
+
+    let mut table = plain_table();
+
+    table.add_row(Row::new(vec![
+        Cell::new("ID").style_spec("Fc"),
+        Cell::new("Time").style_spec("Fc"),
+        Cell::new("Changed").style_spec("Fc"),
+        Cell::new("Command").style_spec("Fc"),
+    ]));
+
+    for entry in entries {
+        let keys = changed_keys(&entry.before, &entry.after);
+        let mut changed = keys
+            .iter()
+            .take(SHOWN_KEYS)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ");
+        if keys.len() > SHOWN_KEYS {
+            changed += &format!(" (+{} more)", keys.len() - SHOWN_KEYS);
+        }
+
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.id.to_string()).style_spec("Fg"),
+            Cell::new(&entry.time),
+            Cell::new(&changed).style_spec("Fy"),
+            Cell::new(&entry.command).style_spec("Fw"),
+        ]));
+    }
+
+    table.printstd();
+    Ok(())
+}
+
+pub async fn rollback(args: ConfigRollbackArgs) -> Result<()> {
+    let client = Client::new();
+    let device = args.device.as_str();
+
+    let entry = open()?
+        .query_row(
+            "SELECT id, time, device, command, before, after FROM changes WHERE id = ?1",
+            [args.to],
+            parse_entry,
+        )
+        .optional()?
+        .ok_or_else(|| anyhow!("No history entry {}", args.to))?;
+    let id = device_id(&client, device).await?;
+    if entry.device != id {
+        bail!(
+            "History entry {} belongs to {}, not {} ({})",
+            entry.id,
+            entry.device,
+            device,
+            id
+        );
+    }
+
+    let rev = cfg_rev(&client, device).await?;
+    let current = fetch_config(&client, device).await?;
+    let changes = plan(&current, &entry.before);
+
+    if changes.is_empty() {
+        println!(
+            "✅ {} already matches the config before change {}",
+            device, entry.id
+        );
+        return Ok(());
+    }
+
+    print_plan(device, &changes);
+
+    if !args.yes && !confirm("Apply? [y/N]: ", false)? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let current_rev = cfg_rev(&client, device).await?;
+    if current_rev != rev {
+        bail!(
+            "Config of {} changed while planning (cfg_rev {} → {}), nothing was changed",
+            device,
+            rev,
+            current_rev
+        );
+    }
+
+    apply_changes(&client, device, &changes).await
+}
@@ -0,0 +1,105 @@
+use crate::config::tree::load_tree;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Fleet profile; each layer is laid out like Shelly.GetConfig.
+///
+/// ```toml
+/// [base.cloud]
+/// enable = false
+///
+/// [groups.office]
+/// devices = ["10.0.0.21", "10.0.0.22"]
+/// [groups.office.config.mqtt]
+/// topic_prefix = "office"
+///
+/// [devices."10.0.0.22"."switch:0"]
+/// name = "Desk lamp"
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    base: Map<String, Value>,
+    #[serde(default)]
+    groups: BTreeMap<String, Group>,
+    #[serde(default)]
+    devices: BTreeMap<String, Map<String, Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Group {
+    #[serde(default)]
+    devices: Vec<String>,
+    #[serde(default)]
+    config: Map<String, Value>,
+}
+
+impl Profile {
+    pub fn load(path: &str) -> Result<Self> {
+        serde_json::from_value(load_tree(path)?).map_err(|e| anyhow!("{}: {}", path, e))
+    }
+
+    /// Expands `@group` and plain device targets; no targets selects every known device.
+    pub fn members(&self, targets: &[String]) -> Result<Vec<String>> {
+        let mut members: Vec<String> = Vec::new();
+        let mut add = |device: &String| {
+            if !members.contains(device) {
+                members.push(device.clone());
+            }
+        };
+
+        if targets.is_empty() {
+            self.groups
+                .values()
+                .flat_map(|g| &g.devices)
+                .for_each(&mut add);
+            self.devices.keys().for_each(&mut add);
+        }
+        for target in targets {
+            match target.strip_prefix('@') {
+                Some(name) => match self.groups.get(name) {
+                    Some(group) => group.devices.iter().for_each(&mut add),
+                    None => bail!(
+                        "Unknown group '{}', known groups: {}",
+                        name,
+                        self.groups.keys().cloned().collect::<Vec<_>>().join(", ")
+                    ),
+                },
+                None => add(target),
+            }
+        }
+        Ok(members)
+    }
+
+    /// Merges base, then the device's groups in name order, then its own overrides.
+    pub fn render(&self, device: &str) -> Value {
+        let mut config = Value::Object(self.base.clone());
+        for group in self.groups.values() {
+            if group.devices.iter().any(|d| d == device) {
+                merge(&mut config, &group.config);
+            }
+        }
+        if let Some(overrides) = self.devices.get(device) {
+            merge(&mut config, overrides);
+        }
+        config
+    }
+}
+
+/// Deep-merges `layer` into `into`; later layers win for scalars and arrays.
+fn merge(into: &mut Value, layer: &Map<String, Value>) {
+    let Some(target) = into.as_object_mut() else {
+        *into = Value::Object(layer.clone());
+        return;
+    };
+    for (key, value) in layer {
+        match (target.get_mut(key), value) {
+            (Some(existing @ Value::Object(_)), Value::Object(nested)) => merge(existing, nested),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
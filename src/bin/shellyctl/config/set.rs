@@ -1,44 +1,83 @@
 use crate::cli::ConfigSetArgs;
-use anyhow::{bail, Result};
-use log::{error, info};
+use crate::config::apply::plan;
+use crate::config::dump::fetch_config;
+use crate::config::history;
+use crate::config::tree::{is_ignored, WRITE_ONLY_KEYS};
+use crate::path::{escape_key, Path, Segment};
+use crate::rpc::{call, rpc_url};
+use anyhow::{anyhow, bail, Result};
+use log::{debug, error, info, warn};
 use reqwest::Client;
 use serde_json::{json, to_string_pretty, Map, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
 
-fn parse_value(val: &str) -> Value {
-    if val.eq_ignore_ascii_case("true") {
+/// Time a rebooting device needs before it stops answering.
+const REBOOT_GRACE: Duration = Duration::from_secs(3);
+/// Interval and per-request timeout while waiting for a device to return.
+const REBOOT_POLL: Duration = Duration::from_secs(2);
+/// How long a device may take to come back after Shelly.Reboot.
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Parses `key=value`, `key:=<json>`, `key=@file` (text) and `key:=@file.json` (JSON).
+pub fn parse_pair(pair: &str) -> Result<(String, Value)> {
+    let Some((key, raw)) = pair.split_once('=') else {
+        bail!("Invalid key=value pair: {}", pair);
+    };
+
+    let read = |path: &str| {
+        std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))
+    };
+
+    if let Some(key) = key.strip_suffix(':') {
+        let text = match raw.strip_prefix('@') {
+            Some(path) => read(path)?,
+            None => raw.to_string(),
+        };
+        let value = serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Invalid JSON value for '{}': {}", key, e))?;
+        Ok((key.to_string(), value))
+    } else if let Some(path) = raw.strip_prefix('@') {
+        Ok((key.to_string(), Value::String(read(path)?)))
+    } else {
+        Ok((key.to_string(), parse_value(raw)))
+    }
+}
+
+/// Guesses the type of a plain value; quoting it ("123" or '123') keeps it a string.
+pub fn parse_value(val: &str) -> Value {
+    let quoted = val.len() >= 2
+        && ((val.starts_with('"') && val.ends_with('"'))
+            || (val.starts_with('\'') && val.ends_with('\'')));
+
+    if quoted {
+        Value::String(val[1..val.len() - 1].to_string())
+    } else if val.eq_ignore_ascii_case("true") {
         Value::Bool(true)
     } else if val.eq_ignore_ascii_case("false") {
         Value::Bool(false)
     } else if let Ok(n) = val.parse::<i64>() {
         Value::Number(n.into())
     } else if let Ok(f) = val.parse::<f64>() {
-        Value::Number(serde_json::Number::from_f64(f).unwrap())
+        serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(val.to_string()))
     } else {
         Value::String(val.to_string())
     }
 }
 
-fn insert_nested_key(config: &mut Map<String, Value>, key: &str, value: &Value) {
-    let mut parts = key.trim_start_matches('.').split('.').peekable();
-    let mut current = config;
-    while let Some(part) = parts.next() {
-        if parts.peek().is_none() {
-            current.insert(part.to_string(), value.clone());
-        } else {
-            current = current
-                .entry(part)
-                .or_insert_with(|| json!({}))
-                .as_object_mut()
-                .expect("intermediate key is not an object");
-        }
+fn with_id(mut params: Value, id: Option<u64>) -> Value {
+    if let Some(id) = id {
+        params["id"] = json!(id);
     }
+    params
 }
 
-pub async fn handle(args: ConfigSetArgs) -> Result<()> {
-    let client = Client::new();
-
-    // First, get available methods
-    let list_url = format!("http://{}/rpc/Shelly.ListMethods", args.device);
+/// Fetches the RPC methods the device supports.
+pub async fn list_methods(client: &Client, device: &str) -> Result<Vec<String>> {
+    let list_url = rpc_url(device, "Shelly.ListMethods");
     let method_list: Value = client
         .post(&list_url)
         .json(&json!({}))
@@ -50,85 +89,379 @@ pub async fn handle(args: ConfigSetArgs) -> Result<()> {
     let Some(methods) = method_list.get("methods") else {
         bail!("Missing 'methods' in Shelly.ListMethods response");
     };
-    let available_methods: Vec<String> = methods
+    Ok(methods
         .as_array()
         .unwrap_or(&vec![])
         .iter()
         .filter_map(|m| m.as_str().map(|s| s.to_string()))
+        .collect())
+}
+
+/// Reads the config revision, which the device bumps on every config change.
+pub async fn cfg_rev(client: &Client, device: &str) -> Result<u64> {
+    call(client, device, "Sys.GetStatus", json!({}))
+        .await?
+        .get("cfg_rev")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("Missing 'cfg_rev' in Sys.GetStatus response"))
+}
+
+/// Splits a config key such as `switch:0` into component and instance id.
+pub fn split_component(key: &str) -> Result<(String, Option<u64>)> {
+    // Components with several instances are addressed as e.g. switch:0
+    match key.split_once(':') {
+        Some((component, id)) => match id.parse::<u64>() {
+            Ok(id) => Ok((component.to_ascii_lowercase(), Some(id))),
+            Err(_) => bail!("Invalid component id: {}", key),
+        },
+        None => Ok((key.to_ascii_lowercase(), None)),
+    }
+}
+
+/// Finds `<component>.<suffix>` among the available methods, case-insensitively.
+pub fn find_method(methods: &[String], component: &str, suffix: &str) -> Option<String> {
+    let wanted = format!("{}.{}", component, suffix).to_lowercase();
+    methods.iter().find(|m| m.to_lowercase() == wanted).cloned()
+}
+
+/// Reads the current config of one component with `<Component>.GetConfig`.
+pub async fn get_config(
+    client: &Client,
+    device: &str,
+    method: &str,
+    id: Option<u64>,
+) -> Result<Value> {
+    let get_url = rpc_url(device, method);
+    Ok(client
+        .post(&get_url)
+        .json(&with_id(json!({}), id))
+        .send()
+        .await?
+        .json()
+        .await?)
+}
+
+/// Sends a partial config with `<Component>.SetConfig` and returns the response.
+pub async fn set_config(
+    client: &Client,
+    device: &str,
+    method: &str,
+    id: Option<u64>,
+    config: Map<String, Value>,
+) -> Result<Value> {
+    let url = rpc_url(device, method);
+    let body = with_id(json!({ "config": config }), id);
+
+    info!("POST {}\nBODY:\n{}", url, to_string_pretty(&body)?);
+
+    let resp = client.post(&url).json(&body).send().await?;
+
+    if !resp.status().is_success() {
+        bail!("{}", resp.text().await?);
+    }
+    Ok(resp.json().await.unwrap_or(Value::Null))
+}
+
+/// Levenshtein distance, used to suggest keys for typos.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(row[j + 1])
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// Formats a "did you mean" hint from the closest `candidates`, if any are close.
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> String {
+    let max = (key.len() / 3).max(2);
+    let mut close: Vec<(usize, &str)> = candidates
+        .map(|c| (edit_distance(key, c), c))
+        .filter(|(d, _)| *d <= max)
         .collect();
+    close.sort();
+    match close.first() {
+        Some((_, c)) => format!(" (did you mean '{}'?)", c),
+        None => String::new(),
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Checks `config` against the current component config.
+///
+/// Unknown keys are returned as errors, except write-only ones the device never
+/// reports; type mismatches are printed as warnings.
+fn validate(prefix: &str, config: &Map<String, Value>, current: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (key, value) in config {
+        let path = format!("{}.{}", prefix, escape_key(key));
+        match current.get(key) {
+            None if current.is_object() && is_ignored(&path, WRITE_ONLY_KEYS) => {}
+            None => {
+                let siblings = current
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(k, _)| k.as_str());
+                errors.push(format!("Unknown key '{}'{}", path, suggest(key, siblings)));
+            }
+            Some(Value::Object(_)) if value.is_object() => {
+                let nested = value.as_object().expect("checked above");
+                errors.extend(validate(&path, nested, &current[key]));
+            }
+            Some(cur)
+                if !cur.is_null() && !value.is_null() && json_type(cur) != json_type(value) =>
+            {
+                let hint = if cur.is_string() && (value.is_number() || value.is_boolean()) {
+                    " (quote the value to keep it a string)"
+                } else {
+                    ""
+                };
+                eprintln!(
+                    "⚠️  '{}' is currently {} but the new value is {}{}",
+                    path,
+                    json_type(cur),
+                    json_type(value),
+                    hint
+                );
+            }
+            Some(_) => {}
+        }
+    }
+    errors
+}
+
+pub async fn handle(args: ConfigSetArgs) -> Result<()> {
+    let client = Client::new();
+
+    // First, get available methods
+    let available_methods = list_methods(&client, &args.device).await?;
+
+    // Remember the revision the validation below is based on
+    let rev = cfg_rev(&client, &args.device).await?;
 
     // Group each KVP independently per RPC
-    let mut by_rpc: Vec<(String, String, Value)> = vec![];
+    let mut by_rpc: BTreeMap<String, Vec<(Path, Value)>> = BTreeMap::new();
+    let mut errors = 0;
 
     for pair in &args.pairs {
-        if let Some((rpc_key, v)) = pair.split_once('=') {
-            if let Some((prefix, keypath)) = rpc_key.split_once('.') {
-                let rpc = prefix.to_ascii_lowercase();
-                let key = keypath.to_string();
-                let value = parse_value(v);
-                by_rpc.push((rpc, key, value));
-            } else {
-                eprintln!("❌ Invalid key format (missing dot): {}", pair);
+        let parsed = parse_pair(pair).and_then(|(key, value)| Ok((Path::parse(&key)?, value)));
+        match parsed {
+            Ok((path, value)) => match path.split_first() {
+                Some((Segment::Key(rpc), keypath)) if !keypath.is_empty() => {
+                    let rpc = rpc.to_ascii_lowercase();
+                    by_rpc.entry(rpc).or_default().push((keypath, value));
+                }
+                _ => {
+                    eprintln!("❌ Invalid key format (expected component.key): {}", pair);
+                    errors += 1;
+                }
+            },
+            Err(err) => {
+                eprintln!("❌ {}", err);
+                errors += 1;
             }
-        } else {
-            eprintln!("❌ Invalid key=value pair: {}", pair);
         }
     }
 
-    use std::collections::HashMap;
-    let mut rpc_configs: HashMap<String, Map<String, Value>> = HashMap::new();
-    for (rpc, key, value) in by_rpc {
-        let map = rpc_configs.entry(rpc.clone()).or_default();
-        insert_nested_key(map, &key, &value);
+    // Resolve and validate everything before sending anything
+    let mut requests = vec![];
+    for (rpc, pairs) in by_rpc {
+        let (component, id) = match split_component(&rpc) {
+            Ok(split) => split,
+            Err(err) => {
+                eprintln!("❌ {}", err);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let Some(set_method) = find_method(&available_methods, &component, "setconfig") else {
+            let components: Vec<String> = available_methods
+                .iter()
+                .filter_map(|m| {
+                    m.to_lowercase()
+                        .strip_suffix(".setconfig")
+                        .map(String::from)
+                })
+                .collect();
+            let hint = suggest(&component, components.iter().map(String::as_str));
+            eprintln!("❌ Method not supported: {}.SetConfig{}", component, hint);
+            errors += 1;
+            continue;
+        };
+
+        let current_config = match find_method(&available_methods, &component, "getconfig") {
+            Some(get_method) => {
+                let current = get_config(&client, &args.device, &get_method, id).await?;
+                match to_string_pretty(&current) {
+                    Ok(pretty) => info!("Current config:\n{}", pretty),
+                    Err(err) => error!("Failed to serialize config: {}", err),
+                }
+                Some(current)
+            }
+            None => {
+                warn!("Cannot validate {}: no GetConfig method", rpc);
+                None
+            }
+        };
+
+        // Array elements are changed on a copy of the current array
+        let mut config = Value::Object(Map::new());
+        for (path, value) in pairs {
+            if let Err(err) = path.set(&mut config, value, current_config.as_ref()) {
+                eprintln!("❌ {}", err);
+                errors += 1;
+            }
+        }
+        let Value::Object(config) = config else {
+            unreachable!("partial config starts as an object");
+        };
+
+        // Validate keys and types with GetConfig
+        if let Some(current_config) = &current_config {
+            for err in validate(&rpc, &config, current_config) {
+                eprintln!("❌ {}", err);
+                errors += 1;
+            }
+        }
+
+        requests.push((rpc, set_method, id, config));
     }
 
-    for (rpc, config) in rpc_configs {
-        // Search for matching methods case-insensitively
-        let set_method = available_methods
-            .iter()
-            .find(|m| m.to_lowercase() == format!("{}.setconfig", rpc))
-            .cloned();
-        let get_method = available_methods
-            .iter()
-            .find(|m| m.to_lowercase() == format!("{}.getconfig", rpc))
-            .cloned();
+    if errors > 0 {
+        bail!("{} invalid setting(s), nothing was changed", errors);
+    }
 
-        let Some(set_method) = set_method else {
-            eprintln!("❌ Method not supported: {}.SetConfig", rpc);
+    if !args.dry_run {
+        let current_rev = cfg_rev(&client, &args.device).await?;
+        if current_rev != rev {
+            bail!(
+                "Config of {} changed while preparing the update (cfg_rev {} → {}), nothing was changed",
+                args.device,
+                rev,
+                current_rev
+            );
+        }
+    }
+
+    let before = if args.dry_run {
+        Value::Null
+    } else {
+        fetch_config(&client, &args.device).await?
+    };
+
+    let mut restart = vec![];
+    for (rpc, set_method, id, config) in requests {
+        if args.dry_run {
+            let body = with_id(json!({ "config": config }), id);
+            println!(
+                "POST {}\n{}",
+                rpc_url(&args.device, &set_method),
+                to_string_pretty(&body)?
+            );
             continue;
-        };
+        }
 
-        // Optional: validate keys with GetConfig
-        if let Some(get_method) = get_method {
-            let get_url = format!("http://{}/rpc/{}", args.device, get_method);
-            let current_config: Value = client
-                .post(&get_url)
-                .json(&json!({}))
-                .send()
-                .await?
-                .json()
-                .await?;
-
-            match to_string_pretty(&current_config) {
-                Ok(pretty) => info!("Current config:\n{}", pretty),
-                Err(err) => error!("Failed to serialize config: {}", err),
+        match set_config(&client, &args.device, &set_method, id, config.clone()).await {
+            Ok(resp) if restart_required(&resp) => {
+                println!(
+                    "✅ {} updated on {} (restart required)",
+                    set_method, args.device
+                );
+                restart.push((rpc, set_method, id, config));
             }
+            Ok(_) => println!("✅ {} updated on {}", set_method, args.device),
+            Err(err) => eprintln!("❌ Failed to update {}: {}", set_method, err),
         }
+    }
 
-        let url = format!("http://{}/rpc/{}", args.device, set_method);
-        let body = json!({ "config": config });
+    if args.dry_run {
+        return Ok(());
+    }
+    history::record(&client, &args.device, &before).await;
 
-        info!("POST {}\nBODY:\n{}", url, to_string_pretty(&body)?);
+    if restart.is_empty() {
+        return Ok(());
+    }
+    if !args.reboot {
+        eprintln!(
+            "⚠️  {} must be restarted for the changes to take effect (use --reboot)",
+            args.device
+        );
+        return Ok(());
+    }
 
-        let resp = client.post(&url).json(&body).send().await?;
+    reboot_and_wait(&client, &args.device).await?;
 
-        if resp.status().is_success() {
-            println!("✅ {} updated on {}", set_method, args.device);
-        } else {
-            let err = resp.text().await?;
-            eprintln!("❌ Failed to update {}: {}", set_method, err);
+    // Confirm the settings survived the restart
+    let mut mismatched = 0;
+    for (rpc, set_method, id, config) in restart {
+        let component = set_method.split_once('.').map_or("", |(c, _)| c);
+        let Some(get_method) = find_method(&available_methods, component, "getconfig") else {
+            continue;
+        };
+        let current = get_config(&client, &args.device, &get_method, id).await?;
+        // Compare under the component key, so write-only keys are recognised
+        for d in plan(&json!({ &rpc: current }), &json!({ &rpc: config })) {
+            eprintln!("❌ {} did not take effect: {}", set_method, d.key());
+            mismatched += 1;
         }
     }
+    if mismatched > 0 {
+        bail!("{} setting(s) differ after reboot", mismatched);
+    }
+    println!("✅ {} is back online with the new settings", args.device);
 
     Ok(())
 }
+
+/// Whether a SetConfig response asks for a reboot to apply the change.
+pub fn restart_required(resp: &Value) -> bool {
+    resp["restart_required"].as_bool().unwrap_or(false)
+}
+
+/// Reboots `device` and waits until it answers Shelly.GetDeviceInfo again.
+pub async fn reboot_and_wait(client: &Client, device: &str) -> Result<()> {
+    call(client, device, "Shelly.Reboot", json!({})).await?;
+    println!("Rebooting {} ...", device);
+
+    // Give the device a moment to actually go down before polling
+    sleep(REBOOT_GRACE).await;
+
+    let poll = Client::builder().timeout(REBOOT_POLL).build()?;
+    let deadline = Instant::now() + REBOOT_TIMEOUT;
+    loop {
+        match call(&poll, device, "Shelly.GetDeviceInfo", json!({})).await {
+            Ok(_) => return Ok(()),
+            Err(err) if Instant::now() < deadline => debug!("{} not up yet: {}", device, err),
+            Err(err) => bail!(
+                "{} did not come back within {}s: {}",
+                device,
+                REBOOT_TIMEOUT.as_secs(),
+                err
+            ),
+        }
+        sleep(REBOOT_POLL).await;
+    }
+}
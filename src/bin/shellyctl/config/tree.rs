@@ -0,0 +1,195 @@
+use anyhow::{anyhow, Result};
+use colored::*;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// How a single key differs between two config trees.
+pub enum Change {
+    Added(Value),
+    Removed(Value),
+    Changed(Value, Value),
+}
+
+pub struct Difference {
+    pub path: Vec<String>,
+    pub change: Change,
+}
+
+impl Difference {
+    pub fn key(&self) -> String {
+        self.path.join(".")
+    }
+}
+
+/// Loads a config tree from a JSON, YAML or TOML file, picked by extension.
+pub fn load_tree(path: &str) -> Result<Value> {
+    let data = fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let tree = match ext.as_str() {
+        "toml" => toml::from_str(&data).map_err(|e| anyhow!("{}: {}", path, e))?,
+        "yaml" | "yml" => serde_norway::from_str(&data).map_err(|e| anyhow!("{}: {}", path, e))?,
+        _ => serde_json::from_str(&data).map_err(|e| anyhow!("{}: {}", path, e))?,
+    };
+    Ok(tree)
+}
+
+/// Compares two trees key by key; arrays and scalars are compared as whole values.
+pub fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    let mut out = Vec::new();
+    diff_into(old, new, &mut Vec::new(), &mut out);
+    out.sort_by(|a, b| a.path.cmp(&b.path));
+    out
+}
+
+fn diff_into(old: &Value, new: &Value, path: &mut Vec<String>, out: &mut Vec<Difference>) {
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (k, va) in a {
+                path.push(k.clone());
+                match b.get(k) {
+                    Some(vb) => diff_into(va, vb, path, out),
+                    None => out.push(Difference {
+                        path: path.clone(),
+                        change: Change::Removed(va.clone()),
+                    }),
+                }
+                path.pop();
+            }
+            for (k, vb) in b {
+                if !a.contains_key(k) {
+                    path.push(k.clone());
+                    out.push(Difference {
+                        path: path.clone(),
+                        change: Change::Added(vb.clone()),
+                    });
+                    path.pop();
+                }
+            }
+        }
+        _ if !same(old, new) => out.push(Difference {
+            path: path.clone(),
+            change: Change::Changed(old.clone(), new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Value equality that treats `60` and `60.0` as the same number.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| same(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, v)| y.get(k).map(|w| same(v, w)).unwrap_or(false))
+        }
+        _ => a == b,
+    }
+}
+
+/// Sets `value` at `path` inside `map`, creating intermediate objects.
+pub fn insert_path(map: &mut Map<String, Value>, path: &[String], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut current = map;
+    for part in parents {
+        let entry = current.entry(part.clone()).or_insert_with(|| json!({}));
+        if !entry.is_object() {
+            *entry = json!({});
+        }
+        current = entry.as_object_mut().expect("just made an object");
+    }
+    current.insert(last.clone(), value);
+}
+
+/// Keys the device accepts but never returns in its config, such as passwords.
+pub const WRITE_ONLY_KEYS: &[&str] = &["*.pass"];
+
+/// Keys the device reports but SetConfig cannot change, some of them on every write.
+pub const READ_ONLY_KEYS: &[&str] = &["sys.cfg_rev", "sys.device.mac", "sys.device.fw_id"];
+
+/// A key is ignored when a pattern matches it or one of its parent keys.
+pub fn is_ignored<P: AsRef<str>>(key: &str, patterns: &[P]) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.as_ref().trim_start_matches('.');
+        std::iter::once(key)
+            .chain(key.match_indices('.').map(|(i, _)| &key[..i]))
+            .any(|k| wildcard_match(pattern, k))
+    })
+}
+
+/// Matches `text` against `pattern`, where `*` stands for any run of characters.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = rest.split('*').collect();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return remaining.ends_with(part);
+        }
+        match remaining.find(part) {
+            Some(pos) => remaining = &remaining[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Prints differences grouped by component, e.g. `~ switch:0` followed by its keys.
+pub fn print_changes(changes: &[Difference]) {
+    let mut by_component = BTreeMap::<&str, Vec<&Difference>>::new();
+    for d in changes {
+        let component = d.path.first().map(String::as_str).unwrap_or_default();
+        by_component.entry(component).or_default().push(d);
+    }
+
+    for (component, diffs) in by_component {
+        let marker = if diffs.iter().all(|d| matches!(d.change, Change::Added(_))) {
+            "+".green()
+        } else if diffs.iter().all(|d| matches!(d.change, Change::Removed(_))) {
+            "-".red()
+        } else {
+            "~".yellow()
+        };
+        println!("  {} {}", marker, component.white().bold());
+
+        for d in diffs {
+            let key = if d.path.len() > 1 {
+                d.path[1..].join(".")
+            } else {
+                component.to_string()
+            };
+            match &d.change {
+                Change::Added(v) => {
+                    println!("      {} {}: {}", "+".green(), key, v.to_string().green())
+                }
+                Change::Removed(v) => {
+                    println!("      {} {}: {}", "-".red(), key, v.to_string().red())
+                }
+                Change::Changed(old, new) => println!(
+                    "      {} {}: {} → {}",
+                    "~".yellow(),
+                    key,
+                    old.to_string().red(),
+                    new.to_string().green()
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use crate::cli::ConfigWatchArgs;
+use crate::config::dump::fetch_config;
+use crate::config::set::cfg_rev;
+use crate::config::tree::{diff, is_ignored, print_changes};
+use anyhow::Result;
+use chrono::Local;
+use reqwest::Client;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Bumped by every write, so never worth reporting.
+const ALWAYS_IGNORED: &[&str] = &["sys.cfg_rev"];
+
+pub async fn handle(args: ConfigWatchArgs) -> Result<()> {
+    let client = Client::new();
+    let device = args.device.as_str();
+
+    let mut rev = cfg_rev(&client, device).await?;
+    let mut config = fetch_config(&client, device).await?;
+    let mut reachable = true;
+    println!("Watching {} at cfg_rev {} (Ctrl-C to stop)", device, rev);
+
+    loop {
+        sleep(Duration::from_secs(args.interval)).await;
+
+        // A revision bump and the config read after it can fail independently
+        let latest = match cfg_rev(&client, device).await {
+            Ok(new_rev) if new_rev == rev => Ok(None),
+            Ok(new_rev) => fetch_config(&client, device)
+                .await
+                .map(|c| Some((new_rev, c))),
+            Err(err) => Err(err),
+        };
+
+        let (new_rev, new_config) = match latest {
+            Ok(None) => {
+                reachable = true;
+                continue;
+            }
+            Ok(Some(latest)) => {
+                reachable = true;
+                latest
+            }
+            Err(err) => {
+                if reachable {
+                    eprintln!("⚠️  {} unreachable: {}", device, err);
+                }
+                reachable = false;
+                continue;
+            }
+        };
+
+        let changes: Vec<_> = diff(&config, &new_config)
+            .into_iter()
+            .filter(|d| {
+                !is_ignored(&d.key(), ALWAYS_IGNORED) && !is_ignored(&d.key(), &args.ignore)
+            })
+            .collect();
+
+        println!(
+            "\n[{}] cfg_rev {} → {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            rev,
+            new_rev
+        );
+        if changes.is_empty() {
+            println!("  (no visible changes)");
+        } else {
+            print_changes(&changes);
+        }
+
+        rev = new_rev;
+        config = new_config;
+    }
+}
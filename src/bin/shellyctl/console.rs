@@ -0,0 +1,35 @@
+//! Where log output goes: stderr, or the browse TUI while it owns the terminal.
+
+use std::io::{self, Write};
+use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+
+static DIVERTED: Mutex<Option<UnboundedSender<String>>> = Mutex::new(None);
+
+/// Sends log lines to `to` instead of stderr, until called with `None`.
+pub fn divert(to: Option<UnboundedSender<String>>) {
+    *DIVERTED.lock().unwrap() = to;
+}
+
+/// Log target for env_logger that honours [`divert`].
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &*DIVERTED.lock().unwrap() {
+            Some(tx) => {
+                for line in String::from_utf8_lossy(buf).lines() {
+                    if !line.is_empty() {
+                        let _ = tx.send(line.to_string());
+                    }
+                }
+                Ok(buf.len())
+            }
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
@@ -1,38 +1,105 @@
+mod backup;
 mod browse;
 mod cli;
+mod console;
 mod config {
+    pub mod apply;
+    pub mod diff;
     pub mod dump;
+    pub mod edit;
+    pub mod get;
+    pub mod history;
+    pub mod profile;
     pub mod set;
+    pub mod tree;
+    pub mod watch;
 }
+mod path;
+mod resolve;
+mod rpc;
 mod script {
+    pub mod catalog;
     pub mod download;
+    pub mod inventory;
     pub mod list;
     pub mod upload;
 }
+mod table;
 
 use clap::Parser;
-use cli::{Cli, Commands, ConfigCommand, ScriptCommand};
+use cli::{CatalogCommand, Cli, Commands, ConfigCommand, ScriptCommand};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
     if cli.verbose > 0 {
         env_logger::Builder::from_default_env()
             .filter_level(log::LevelFilter::Debug) // or Info
+            .target(env_logger::Target::Pipe(Box::new(console::LogWriter)))
             .init();
     }
+    resolve::resolve_all(device_args(&mut cli.command)).await;
 
     match cli.command {
         Commands::Script { command } => match command {
             ScriptCommand::Upload(args) => script::upload::handle(args).await?,
             ScriptCommand::Download(args) => script::download::handle(args).await?,
             ScriptCommand::List(args) => script::list::handle(args).await?,
+            ScriptCommand::Inventory(args) => script::inventory::handle(args).await?,
+            ScriptCommand::Catalog { command } => match command {
+                CatalogCommand::List(args) => script::catalog::list(args).await?,
+                CatalogCommand::Show(args) => script::catalog::show(args).await?,
+                CatalogCommand::Install(args) => script::catalog::install(args).await?,
+            },
         },
         Commands::Config { command } => match command {
             ConfigCommand::Set(args) => config::set::handle(args).await?,
             ConfigCommand::Dump(args) => config::dump::handle(args).await?,
+            ConfigCommand::Get(args) => config::get::handle(args).await?,
+            ConfigCommand::Apply(args) => config::apply::handle(args).await?,
+            ConfigCommand::Diff(args) => config::diff::handle(args).await?,
+            ConfigCommand::Watch(args) => config::watch::handle(args).await?,
+            ConfigCommand::Edit(args) => config::edit::handle(args).await?,
+            ConfigCommand::History(args) => config::history::history(args).await?,
+            ConfigCommand::Rollback(args) => config::history::rollback(args).await?,
         },
         Commands::Browse(args) => browse::handle(args).await?,
+        Commands::Backup(args) => backup::backup(args).await?,
+        Commands::Restore(args) => backup::restore(args).await?,
     }
     Ok(())
 }
+
+/// Device names given on the command line, to be resolved before use.
+fn device_args(command: &mut Commands) -> Vec<&mut String> {
+    match command {
+        Commands::Script { command } => match command {
+            ScriptCommand::Upload(args) => vec![&mut args.device],
+            ScriptCommand::Download(args) => vec![&mut args.device],
+            ScriptCommand::List(args) => vec![&mut args.device],
+            ScriptCommand::Inventory(args) => args.devices.iter_mut().collect(),
+            ScriptCommand::Catalog { command } => match command {
+                CatalogCommand::Install(args) => vec![&mut args.device],
+                CatalogCommand::List(_) | CatalogCommand::Show(_) => vec![],
+            },
+        },
+        Commands::Config { command } => match command {
+            ConfigCommand::Set(args) => vec![&mut args.device],
+            ConfigCommand::Dump(args) => vec![&mut args.device],
+            ConfigCommand::Get(args) => vec![&mut args.device],
+            ConfigCommand::Apply(args) => args.device.iter_mut().collect(),
+            // Either side may be a saved snapshot instead
+            ConfigCommand::Diff(args) => [&mut args.left, &mut args.right]
+                .into_iter()
+                .filter(|source| !std::path::Path::new(source.as_str()).is_file())
+                .collect(),
+            ConfigCommand::Watch(args) => vec![&mut args.device],
+            ConfigCommand::Edit(args) => vec![&mut args.device],
+            ConfigCommand::History(args) => vec![&mut args.device],
+            ConfigCommand::Rollback(args) => vec![&mut args.device],
+        },
+        Commands::Browse(_) => vec![],
+        Commands::Backup(args) => vec![&mut args.device],
+        Commands::Restore(args) => vec![&mut args.device],
+    }
+}
@@ -0,0 +1,502 @@
+//! Key paths into Shelly JSON documents, shared by shellyctl and shellymon.
+//!
+//! Paths are dotted, e.g. `switch:0.temperature.tC`. `[n]` or a numeric
+//! segment indexes arrays (`aenergy.by_minute[0]`, `wifi.sta.ip.0`), and a
+//! key holding a dot is written as `a\.b` or quoted as `"a.b"`. Paths that
+//! start with `/` are JSON pointers (RFC 6901).
+//!
+//! This file only depends on std and serde_json so shellymon can include it
+//! with `#[path]`.
+
+use serde_json::{Map, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A parsed path that remembers where each segment came from, for errors.
+#[derive(Debug, Clone)]
+pub struct Path {
+    source: String,
+    segments: Vec<(Segment, usize, usize)>,
+    start: usize,
+}
+
+/// A parse or lookup error pointing at the offending segment.
+#[derive(Debug)]
+pub struct PathError {
+    source: String,
+    span: (usize, usize),
+    message: String,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pad = self.source[..self.span.0].chars().count();
+        let width = self.source[self.span.0..self.span.1].chars().count().max(1);
+        write!(
+            f,
+            "'{}': {}\n  {}\n  {}{}",
+            self.source,
+            self.message,
+            self.source,
+            " ".repeat(pad),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl Path {
+    pub fn parse(source: &str) -> Result<Self, PathError> {
+        let segments = if source.starts_with('/') {
+            parse_pointer(source)
+        } else {
+            parse_dotted(source)?
+        };
+        Ok(Path {
+            source: source.to_string(),
+            segments,
+            start: 0,
+        })
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.segments[self.start..].iter().map(|(s, _, _)| s)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.segments.len()
+    }
+
+    /// Splits off the first segment, keeping positions for errors in the rest.
+    pub fn split_first(&self) -> Option<(&Segment, Path)> {
+        let (first, _, _) = self.segments.get(self.start)?;
+        let rest = Path {
+            start: self.start + 1,
+            ..self.clone()
+        };
+        Some((first, rest))
+    }
+
+    fn error(&self, i: usize, message: String) -> PathError {
+        let (_, from, to) = self.segments[self.start + i];
+        PathError {
+            source: self.source.clone(),
+            span: (from, to),
+            message,
+        }
+    }
+
+    /// Canonical dotted form of the segments before `i`, or `<root>`.
+    fn parent(&self, i: usize) -> String {
+        let parent = format_segments(self.segments[..self.start + i].iter().map(|(s, _, _)| s));
+        if parent.is_empty() {
+            "<root>".to_string()
+        } else {
+            parent
+        }
+    }
+
+    /// Resolves the path inside `root`.
+    pub fn get<'a>(&self, root: &'a Value) -> Result<&'a Value, PathError> {
+        let mut current = root;
+        for (i, segment) in self.segments().enumerate() {
+            current = match (segment, current) {
+                (Segment::Key(key), Value::Object(map)) => map
+                    .get(key)
+                    .ok_or_else(|| format!("no '{}' in {}", key, self.parent(i))),
+                (_, Value::Array(arr)) => match index_of(segment) {
+                    Some(n) => arr.get(n).ok_or_else(|| {
+                        format!(
+                            "index {} out of range, {} has {} element(s)",
+                            n,
+                            self.parent(i),
+                            arr.len()
+                        )
+                    }),
+                    None => Err(format!("{} is an array, expected an index", self.parent(i))),
+                },
+                (_, other) => Err(format!(
+                    "{} is {}, not {}",
+                    self.parent(i),
+                    type_name(other),
+                    expected(segment)
+                )),
+            }
+            .map_err(|message| self.error(i, message))?;
+        }
+        Ok(current)
+    }
+
+    /// Sets `value` at the path inside `root`, creating objects for missing keys.
+    ///
+    /// Arrays missing from `root` are copied from `base` first, so a single
+    /// element of a partial config can be changed.
+    pub fn set(
+        &self,
+        root: &mut Value,
+        value: Value,
+        base: Option<&Value>,
+    ) -> Result<(), PathError> {
+        let mut current = root;
+        let mut base = base;
+        for (i, segment) in self.segments().enumerate() {
+            let child_base = base.and_then(|b| Path::step(b, segment));
+            let parent = self.parent(i);
+
+            current = match current {
+                Value::Object(map) => {
+                    let Segment::Key(key) = segment else {
+                        return Err(self.error(i, format!("{} is an object, not an array", parent)));
+                    };
+                    map.entry(key.clone()).or_insert_with(|| match child_base {
+                        Some(arr @ Value::Array(_)) => arr.clone(),
+                        _ => Value::Object(Map::new()),
+                    })
+                }
+                Value::Array(arr) => {
+                    let len = arr.len();
+                    let Some(n) = index_of(segment) else {
+                        return Err(
+                            self.error(i, format!("{} is an array, expected an index", parent))
+                        );
+                    };
+                    arr.get_mut(n).ok_or_else(|| {
+                        self.error(
+                            i,
+                            format!(
+                                "index {} out of range, {} has {} element(s)",
+                                n, parent, len
+                            ),
+                        )
+                    })?
+                }
+                other => {
+                    let message = format!(
+                        "{} is {}, not {}",
+                        parent,
+                        type_name(other),
+                        expected(segment)
+                    );
+                    return Err(self.error(i, message));
+                }
+            };
+            base = child_base;
+        }
+        *current = value;
+        Ok(())
+    }
+
+    fn step<'a>(value: &'a Value, segment: &Segment) -> Option<&'a Value> {
+        match (segment, value) {
+            (Segment::Key(key), Value::Object(map)) => map.get(key),
+            (_, Value::Array(arr)) => arr.get(index_of(segment)?),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format_segments(self.segments()))
+    }
+}
+
+/// Escapes a key so it reads back as a single dotted segment.
+pub fn escape_key(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    for (i, c) in key.chars().enumerate() {
+        if matches!(c, '.' | '[' | '\\') || (i == 0 && matches!(c, '"' | '\'' | '/')) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn format_segments<'a>(segments: impl Iterator<Item = &'a Segment>) -> String {
+    let mut out = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(&escape_key(key));
+            }
+            Segment::Index(n) => out.push_str(&format!("[{}]", n)),
+        }
+    }
+    out
+}
+
+fn index_of(segment: &Segment) -> Option<usize> {
+    match segment {
+        Segment::Index(n) => Some(*n),
+        Segment::Key(key) => key.parse().ok(),
+    }
+}
+
+fn expected(segment: &Segment) -> &'static str {
+    match segment {
+        Segment::Index(_) => "an array",
+        Segment::Key(_) => "an object",
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// `/a/b~1c/0`: every segment is a key, `~1` is `/` and `~0` is `~`.
+fn parse_pointer(source: &str) -> Vec<(Segment, usize, usize)> {
+    let mut segments = Vec::new();
+    let mut from = 1;
+    for raw in source[1..].split('/') {
+        let key = raw.replace("~1", "/").replace("~0", "~");
+        segments.push((Segment::Key(key), from, from + raw.len()));
+        from += raw.len() + 1;
+    }
+    segments
+}
+
+fn parse_dotted(source: &str) -> Result<Vec<(Segment, usize, usize)>, PathError> {
+    let error = |from: usize, to: usize, message: &str| PathError {
+        source: source.to_string(),
+        span: (from, to),
+        message: message.to_string(),
+    };
+
+    let chars: Vec<(usize, char)> = source.char_indices().collect();
+    let offset = |i: usize| chars.get(i).map_or(source.len(), |(o, _)| *o);
+    let mut segments = Vec::new();
+
+    // A leading dot is allowed, as in `.wifi.ap`
+    let mut i = usize::from(source.starts_with('.'));
+    if i >= chars.len() {
+        return Ok(segments);
+    }
+
+    loop {
+        let start = i;
+        let mut key = String::new();
+
+        // Quoted keys may be empty, bare ones may not
+        let has_key = match chars.get(i).map(|(_, c)| *c) {
+            Some(quote @ ('"' | '\'')) => {
+                i += 1;
+                loop {
+                    match chars.get(i).map(|(_, c)| *c) {
+                        None => {
+                            return Err(error(offset(start), source.len(), "unterminated quote"))
+                        }
+                        Some(c) if c == quote => break,
+                        Some('\\') if chars.get(i + 1).is_some() => {
+                            key.push(chars[i + 1].1);
+                            i += 1;
+                        }
+                        Some(c) => key.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                true
+            }
+            _ => {
+                while let Some(&(_, c)) = chars.get(i) {
+                    match c {
+                        '.' | '[' => break,
+                        '\\' => match chars.get(i + 1) {
+                            Some(&(_, escaped)) => {
+                                key.push(escaped);
+                                i += 1;
+                            }
+                            None => return Err(error(offset(i), source.len(), "dangling '\\'")),
+                        },
+                        c => key.push(c),
+                    }
+                    i += 1;
+                }
+                !key.is_empty()
+            }
+        };
+        if has_key {
+            segments.push((Segment::Key(key), offset(start), offset(i)));
+        }
+
+        let mut has_index = false;
+        while chars.get(i).map(|(_, c)| *c) == Some('[') {
+            let open = i;
+            let digits: String = chars[i + 1..]
+                .iter()
+                .map(|(_, c)| *c)
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            i += 1 + digits.len();
+            if chars.get(i).map(|(_, c)| *c) != Some(']') || digits.is_empty() {
+                let close = chars[open..]
+                    .iter()
+                    .position(|(_, c)| *c == ']')
+                    .map_or(source.len(), |p| offset(open + p + 1));
+                return Err(error(
+                    offset(open),
+                    close,
+                    "expected an array index like [0]",
+                ));
+            }
+            i += 1;
+            let n = digits
+                .parse()
+                .map_err(|_| error(offset(open), offset(i), "array index is too large"))?;
+            segments.push((Segment::Index(n), offset(open), offset(i)));
+            has_index = true;
+        }
+
+        if !has_key && !has_index {
+            return Err(error(offset(start), offset(start + 1), "empty segment"));
+        }
+
+        match chars.get(i).map(|(_, c)| *c) {
+            None => break,
+            Some('.') => {
+                i += 1;
+                if i >= chars.len() {
+                    return Err(error(offset(i - 1), source.len(), "empty segment"));
+                }
+            }
+            Some(_) => {
+                return Err(error(offset(i), offset(i + 1), "expected '.' or '[' here"));
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(k: &str) -> Segment {
+        Segment::Key(k.to_string())
+    }
+
+    fn segments(source: &str) -> Vec<Segment> {
+        Path::parse(source)
+            .unwrap_or_else(|e| panic!("{}", e))
+            .segments()
+            .cloned()
+            .collect()
+    }
+
+    fn parse_error(source: &str) -> String {
+        match Path::parse(source) {
+            Ok(path) => panic!("'{}' parsed as {:?}", source, path.segments),
+            Err(e) => e.message,
+        }
+    }
+
+    #[test]
+    fn indexes_in_brackets() {
+        assert_eq!(segments("a[0].b"), [key("a"), Segment::Index(0), key("b")]);
+        assert_eq!(
+            segments("a[1][2]"),
+            [key("a"), Segment::Index(1), Segment::Index(2)]
+        );
+    }
+
+    #[test]
+    fn numeric_segments_index_arrays() {
+        assert_eq!(
+            segments("wifi.sta.ip.0"),
+            [key("wifi"), key("sta"), key("ip"), key("0")]
+        );
+        let doc = json!({ "wifi": { "sta": { "ip": ["10.0.0.2"] } } });
+        let path = Path::parse("wifi.sta.ip.0").unwrap();
+        assert_eq!(path.get(&doc).unwrap(), "10.0.0.2");
+    }
+
+    #[test]
+    fn escaped_and_quoted_dots() {
+        assert_eq!(segments(r"a\.b"), [key("a.b")]);
+        assert_eq!(segments(r#""a.b".c"#), [key("a.b"), key("c")]);
+        assert_eq!(segments("'a.b'"), [key("a.b")]);
+        assert_eq!(segments(".wifi.ap"), [key("wifi"), key("ap")]);
+    }
+
+    #[test]
+    fn json_pointers() {
+        assert_eq!(segments("/a~1b/0"), [key("a/b"), key("0")]);
+        assert_eq!(segments("/a~0b"), [key("a~b")]);
+    }
+
+    #[test]
+    fn malformed_paths_are_rejected() {
+        assert_eq!(parse_error("a..b"), "empty segment");
+        assert_eq!(parse_error("a."), "empty segment");
+        assert_eq!(parse_error("a["), "expected an array index like [0]");
+        assert_eq!(parse_error("a[x]"), "expected an array index like [0]");
+        assert_eq!(parse_error(r"a\"), "dangling '\\'");
+        assert_eq!(parse_error(r#""a"#), "unterminated quote");
+        assert_eq!(parse_error("a[0]b"), "expected '.' or '[' here");
+    }
+
+    #[test]
+    fn error_points_at_the_segment() {
+        let err = Path::parse("wifi.sta[x]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'wifi.sta[x]': expected an array index like [0]\n  wifi.sta[x]\n          ^^^"
+        );
+
+        let doc = json!({ "wifi": { "sta": {} } });
+        let err = Path::parse("wifi.sta.ssid").unwrap().get(&doc).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'wifi.sta.ssid': no 'ssid' in wifi.sta\n  wifi.sta.ssid\n           ^^^^"
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for source in [
+            "a[0].b",
+            r"a\.b",
+            r#""a.b".c"#,
+            "/a~1b/0",
+            "sw:0.x[2]",
+            r"a\\b",
+        ] {
+            let shown = Path::parse(source).unwrap().to_string();
+            assert_eq!(segments(&shown), segments(source), "{} → {}", source, shown);
+        }
+    }
+
+    #[test]
+    fn escaped_keys_parse_back() {
+        for k in [
+            "a.b",
+            "a[0]",
+            r"back\slash",
+            "\"quoted",
+            "'single",
+            "/slash",
+            "plain",
+        ] {
+            assert_eq!(segments(&escape_key(k)), [key(k)], "{}", escape_key(k));
+        }
+    }
+}
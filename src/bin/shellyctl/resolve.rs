@@ -0,0 +1,237 @@
+//! Device names to addresses, for hosts without mDNS name resolution.
+//!
+//! `browse` remembers every device it finds in a cache. Names given to `-d`,
+//! like `shellyplus1pm-a8032ab12345` or `shellyplus1pm-a8032ab12345.local`,
+//! are looked up there first, then with a direct mDNS query. A cached address
+//! where a different device, or none, answers is looked up over mDNS again.
+//!
+//! shellymon includes this file with `#[path]` to resolve its device addresses.
+
+use crate::rpc::device_url;
+use anyhow::{anyhow, Result};
+use log::debug;
+use mdns_sd::{HostnameResolutionEvent, ServiceDaemon};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::task::JoinSet;
+use tokio::time::{timeout_at, Instant};
+
+/// How long a name gets to answer a direct mDNS query.
+const MDNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a cached address gets to identify itself before mDNS is asked.
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A device as last seen by `browse`, keyed by hostname in the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cached {
+    pub ip: String,
+    pub id: String,
+}
+
+/// Location of the cache, `$XDG_CACHE_HOME/shellyctl/devices.json` by default.
+fn cache_path() -> Result<PathBuf> {
+    let cache_dir = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = std::env::var_os("HOME").ok_or_else(|| anyhow!("$HOME is not set"))?;
+            PathBuf::from(home).join(".cache")
+        }
+    };
+    Ok(cache_dir.join("shellyctl").join("devices.json"))
+}
+
+/// Reads the cache; a missing or unreadable one is empty.
+pub fn load() -> BTreeMap<String, Cached> {
+    cache_path()
+        .and_then(|path| Ok(fs::read_to_string(path)?))
+        .and_then(|data| Ok(serde_json::from_str(&data)?))
+        .unwrap_or_default()
+}
+
+/// Adds or refreshes devices in the cache; failures only warn.
+pub fn remember(devices: impl IntoIterator<Item = (String, Cached)>) {
+    if let Err(err) = save(devices) {
+        eprintln!("⚠️  Failed to update the device cache: {}", err);
+    }
+}
+
+/// Adds or refreshes devices in the cache.
+pub fn save(devices: impl IntoIterator<Item = (String, Cached)>) -> Result<()> {
+    let mut cache = load();
+    cache.extend(devices);
+    let path = cache_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write aside and rename, so readers never see a half-written cache
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&tmp, serde_json::to_string_pretty(&cache)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+/// Replaces every name in `devices` with the address to reach it at.
+///
+/// Commands call this once on entry, so URLs are only ever built from
+/// addresses and lookups never block a request.
+pub async fn resolve_all(mut devices: Vec<&mut String>) {
+    let mut lookups = JoinSet::new();
+    for (i, device) in devices.iter().enumerate() {
+        let device = device.to_string();
+        lookups.spawn(async move { (i, resolve(&device).await) });
+    }
+    while let Some(Ok((i, address))) = lookups.join_next().await {
+        *devices[i] = address;
+    }
+}
+
+/// Address to reach `device` at, resolved once per process.
+///
+/// IP addresses and DNS names with dots pass through unchanged, as does any
+/// name neither the cache nor mDNS knows, so the system resolver still gets
+/// a go. A `:port` suffix is kept.
+pub async fn resolve(device: &str) -> String {
+    static RESOLVED: OnceLock<Mutex<HashMap<String, Arc<OnceCell<String>>>>> = OnceLock::new();
+
+    if device.parse::<IpAddr>().is_ok() || device.starts_with('[') {
+        return device.to_string();
+    }
+    let (host, port) = match device.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => (host, port.parse().ok()),
+        _ => (device, None),
+    };
+    let lower = host.to_ascii_lowercase();
+    let name = lower
+        .strip_suffix(".local.")
+        .or_else(|| lower.strip_suffix(".local"))
+        .unwrap_or(&lower);
+    if name.is_empty() || name.contains('.') || name == "localhost" {
+        return device.to_string();
+    }
+
+    // Concurrent lookups of one name share a single query
+    let cell = RESOLVED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(device.to_string())
+        .or_default()
+        .clone();
+    cell.get_or_init(|| async {
+        match lookup(name, port).await {
+            Some(ip) => address(ip, port),
+            None => device.to_string(),
+        }
+    })
+    .await
+    .clone()
+}
+
+/// `ip` with `port` appended, if any, as a URL host expects it.
+fn address(ip: IpAddr, port: Option<u16>) -> String {
+    match (ip, port) {
+        (IpAddr::V6(ip), Some(port)) => format!("[{}]:{}", ip, port),
+        (ip, Some(port)) => format!("{}:{}", ip, port),
+        (ip, None) => ip.to_string(),
+    }
+}
+
+/// Finds `name` in the cache, or over mDNS when the cached address is stale.
+async fn lookup(name: &str, port: Option<u16>) -> Option<IpAddr> {
+    let cache = load();
+    let cached = cache.iter().find(|(hostname, c)| {
+        hostname.eq_ignore_ascii_case(name) || c.id.eq_ignore_ascii_case(name)
+    });
+    let Some((hostname, cached)) = cached else {
+        return query_mdns(name).await;
+    };
+
+    if let Ok(ip) = cached.ip.parse::<IpAddr>() {
+        let info = identify(ip, port).await;
+        if info.is_some_and(|info| is_device(&info, &cached.id)) {
+            debug!("Resolved {} to {} from the device cache", hostname, ip);
+            return Some(ip);
+        }
+    }
+
+    // Most likely a new DHCP lease since browse last saw the device
+    debug!(
+        "Cached address {} of {} does not answer as {}, asking mDNS",
+        cached.ip, hostname, cached.id
+    );
+    let ip = query_mdns(hostname).await?;
+    remember([(
+        hostname.clone(),
+        Cached {
+            ip: ip.to_string(),
+            id: cached.id.clone(),
+        },
+    )]);
+    Some(ip)
+}
+
+/// The `/shelly` identification of whatever answers at `ip`.
+async fn identify(ip: IpAddr, port: Option<u16>) -> Option<Value> {
+    let client = Client::builder().timeout(IDENTIFY_TIMEOUT).build().ok()?;
+    let url = device_url(&address(ip, port), "shelly");
+    client.get(url).send().await.ok()?.json().await.ok()
+}
+
+/// Whether a `/shelly` response comes from the device with `id`.
+///
+/// Gen1 devices report no id, but their default id ends with the last six
+/// digits of their MAC address.
+fn is_device(info: &Value, id: &str) -> bool {
+    match (info["id"].as_str(), info["mac"].as_str()) {
+        (Some(found), _) => found.eq_ignore_ascii_case(id),
+        (None, Some(mac)) if mac.len() >= 6 => id
+            .to_ascii_lowercase()
+            .ends_with(&mac[mac.len() - 6..].to_ascii_lowercase()),
+        _ => false,
+    }
+}
+
+/// Asks the network for `<name>.local.`, preferring an IPv4 answer.
+///
+/// Link-local IPv6 answers are ignored, as they come without a zone.
+async fn query_mdns(name: &str) -> Option<IpAddr> {
+    let mdns = ServiceDaemon::new().ok()?;
+    let hostname = format!("{}.local.", name);
+    let receiver = mdns
+        .resolve_hostname(&hostname, Some(MDNS_TIMEOUT.as_millis() as u64))
+        .ok()?;
+
+    let deadline = Instant::now() + MDNS_TIMEOUT;
+    let mut found = None;
+    while let Ok(Ok(event)) = timeout_at(deadline, receiver.recv_async()).await {
+        match event {
+            HostnameResolutionEvent::AddressesFound(_, addresses) => {
+                found = addresses
+                    .iter()
+                    .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.is_unicast_link_local()))
+                    .min_by_key(|ip| !ip.is_ipv4())
+                    .copied();
+                if found.is_some() {
+                    break;
+                }
+            }
+            HostnameResolutionEvent::SearchTimeout(_) => break,
+            _ => {}
+        }
+    }
+    let _ = mdns.shutdown();
+
+    if let Some(ip) = found {
+        debug!("Resolved {} to {} over mDNS", hostname, ip);
+    }
+    found
+}
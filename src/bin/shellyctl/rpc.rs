@@ -0,0 +1,39 @@
+//! Device RPC over HTTP. shellymon includes this file with `#[path]` for its URLs.
+
+use anyhow::{bail, Result};
+use log::debug;
+use reqwest::Client;
+use serde_json::Value;
+use std::net::Ipv6Addr;
+
+/// URL of `path` on `device`, a hostname or IP address with an optional port.
+///
+/// Shelly names are expected to be resolved already, see
+/// [`crate::resolve::resolve_all`]. Bare IPv6 addresses are bracketed, e.g.
+/// `fd00::12` becomes `http://[fd00::12]/shelly`.
+pub fn device_url(device: &str, path: &str) -> String {
+    if device.parse::<Ipv6Addr>().is_ok() {
+        format!("http://[{}]/{}", device, path)
+    } else {
+        format!("http://{}/{}", device, path)
+    }
+}
+
+/// URL of the RPC endpoint for `method` on `device`.
+pub fn rpc_url(device: &str, method: &str) -> String {
+    device_url(device, &format!("rpc/{}", method))
+}
+
+/// Calls `method` on `device` with JSON `params` and returns the decoded response.
+pub async fn call(client: &Client, device: &str, method: &str, params: Value) -> Result<Value> {
+    let url = rpc_url(device, method);
+    debug!("POST {} {}", url, params);
+
+    let resp = client.post(&url).json(&params).send().await?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        bail!("{} failed: {} {}", method, status, body);
+    }
+    Ok(resp.json().await.unwrap_or(Value::Null))
+}
@@ -0,0 +1,414 @@
+use crate::cli::{CatalogInstallArgs, CatalogListArgs, CatalogShowArgs};
+use crate::config::set::parse_pair;
+use crate::script::upload::upload;
+use crate::table::plain_table;
+use anyhow::{anyhow, bail, Result};
+use colored::*;
+use log::debug;
+use prettytable::{Cell, Row};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+
+/// One entry of a script manifest in the format used by Shelly's script examples.
+#[derive(Debug, Deserialize)]
+struct CatalogEntry {
+    #[serde(default)]
+    id: Option<String>,
+    fname: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    description: String,
+}
+
+impl CatalogEntry {
+    fn id(&self) -> &str {
+        self.id
+            .as_deref()
+            .unwrap_or_else(|| self.fname.strip_suffix(".js").unwrap_or(&self.fname))
+    }
+}
+
+/// A top-level entry of the `CONFIG = { ... }` object literal in a script.
+struct ConfigEntry {
+    key: String,
+    value: Range<usize>,
+}
+
+pub async fn list(args: CatalogListArgs) -> Result<()> {
+    let client = Client::new();
+    let entries = load_manifest(&client, &args.manifest).await?;
+
+    if entries.is_empty() {
+        println!("No scripts found.");
+        return Ok(());
+    }
+
+    let mut table = plain_table();
+
+    table.add_row(Row::new(vec![
+        Cell::new("ID").style_spec("Fc"),
+        Cell::new("Title").style_spec("Fc"),
+    ]));
+
+    for entry in &entries {
+        table.add_row(Row::new(vec![
+            Cell::new(entry.id()).style_spec("Fg"),
+            Cell::new(&entry.title).style_spec("Fw"),
+        ]));
+    }
+
+    table.printstd();
+    Ok(())
+}
+
+pub async fn show(args: CatalogShowArgs) -> Result<()> {
+    let client = Client::new();
+    let entries = load_manifest(&client, &args.manifest).await?;
+    let entry = find_entry(&entries, &args.id)?;
+    let source = resolve_script(&args.manifest, &entry.fname)?;
+    let code = fetch(&client, &source).await?;
+
+    println!("{}", entry.title.bold());
+    if !entry.description.is_empty() {
+        println!("{}", entry.description);
+    }
+    println!();
+    println!("{}: {}", "ID".white(), entry.id().green());
+    println!("{}: {}", "Source".white(), source);
+
+    match config_section(&code) {
+        Some(config) if !config.is_empty() => {
+            println!("{}:", "CONFIG".white());
+            for entry in config {
+                println!("  {}: {}", entry.key, code[entry.value].yellow());
+            }
+        }
+        _ => println!("{}: {}", "CONFIG".white(), "none".italic().dimmed()),
+    }
+
+    if args.code {
+        println!();
+        println!("{}", code);
+    }
+
+    Ok(())
+}
+
+pub async fn install(args: CatalogInstallArgs) -> Result<()> {
+    let client = Client::new();
+    let entries = load_manifest(&client, &args.manifest).await?;
+    let entry = find_entry(&entries, &args.id)?;
+    let source = resolve_script(&args.manifest, &entry.fname)?;
+    let mut code = fetch(&client, &source).await?;
+
+    if !args.set.is_empty() {
+        let mut overrides = Vec::new();
+        for pair in &args.set {
+            let (key, value) = parse_pair(pair)?;
+            overrides.push((key.trim().to_string(), value));
+        }
+        code = apply_overrides(&code, &overrides)?;
+    }
+
+    let name = args.name.clone().unwrap_or_else(|| entry.id().to_string());
+    match upload(&client, &args.device, &name, &code, args.force, args.enable).await? {
+        Some(id) => println!(
+            "✅ Installed '{}' as script '{}' (ID {}) on {}",
+            entry.id(),
+            name,
+            id,
+            args.device
+        ),
+        None => bail!(
+            "Script '{}' is running on {}. Use --force to stop and overwrite.",
+            name,
+            args.device
+        ),
+    }
+
+    Ok(())
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+async fn fetch(client: &Client, location: &str) -> Result<String> {
+    debug!("Fetching {}", location);
+    if is_url(location) {
+        let res = client.get(location).send().await?;
+        if !res.status().is_success() {
+            bail!("Failed to fetch {}: {}", location, res.status());
+        }
+        Ok(res.text().await?)
+    } else {
+        std::fs::read_to_string(location).map_err(|e| anyhow!("Failed to read {}: {}", location, e))
+    }
+}
+
+async fn load_manifest(client: &Client, manifest: &str) -> Result<Vec<CatalogEntry>> {
+    let data = fetch(client, manifest).await?;
+    serde_json::from_str(&data).map_err(|e| anyhow!("Invalid script manifest {}: {}", manifest, e))
+}
+
+fn find_entry<'a>(entries: &'a [CatalogEntry], id: &str) -> Result<&'a CatalogEntry> {
+    entries
+        .iter()
+        .find(|e| e.id() == id || e.fname == id)
+        .ok_or_else(|| anyhow!("Script '{}' not found in manifest", id))
+}
+
+/// Resolves a script file name relative to the manifest it was listed in.
+fn resolve_script(manifest: &str, fname: &str) -> Result<String> {
+    if is_url(fname) {
+        Ok(fname.to_string())
+    } else if is_url(manifest) {
+        Ok(Url::parse(manifest)?.join(fname)?.to_string())
+    } else {
+        let base = Path::new(manifest).parent().unwrap_or(Path::new(""));
+        Ok(base.join(fname).to_string_lossy().into_owned())
+    }
+}
+
+/// Replaces values in the script's `CONFIG` object with the given overrides.
+fn apply_overrides(code: &str, overrides: &[(String, Value)]) -> Result<String> {
+    let entries =
+        config_section(code).ok_or_else(|| anyhow!("Script has no CONFIG section to template"))?;
+
+    // Keyed by position so that repeated keys collapse and replacements run back to front
+    let mut replacements = BTreeMap::<usize, (Range<usize>, String)>::new();
+    for (key, value) in overrides {
+        let Some(entry) = entries.iter().find(|e| &e.key == key) else {
+            let known: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+            bail!(
+                "Unknown CONFIG key '{}' (available: {})",
+                key,
+                known.join(", ")
+            );
+        };
+        debug!("CONFIG.{} = {}", key, value);
+        replacements.insert(
+            entry.value.start,
+            (entry.value.clone(), serde_json::to_string(value)?),
+        );
+    }
+
+    let mut code = code.to_string();
+    for (range, text) in replacements.into_values().rev() {
+        code.replace_range(range, &text);
+    }
+    Ok(code)
+}
+
+/// Locates the `CONFIG = { ... }` literal and returns its top-level entries.
+fn config_section(code: &str) -> Option<Vec<ConfigEntry>> {
+    let b = code.as_bytes();
+    let open = find_config_open(code)?;
+
+    let mut entries = Vec::new();
+    let mut depth = 0usize;
+    let mut i = open;
+    let mut last_end = open;
+    let mut key_start: Option<usize> = None;
+    let mut key: Option<Range<usize>> = None;
+    let mut value_start: Option<usize> = None;
+
+    while i < b.len() {
+        let c = b[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == b'/' && b.get(i + 1) == Some(&b'/') {
+            while i < b.len() && b[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == b'/' && b.get(i + 1) == Some(&b'*') {
+            i = code[i + 2..]
+                .find("*/")
+                .map(|p| i + 2 + p + 2)
+                .unwrap_or(b.len());
+            continue;
+        }
+
+        match c {
+            b'{' if depth == 0 => {
+                depth = 1;
+                i += 1;
+                continue;
+            }
+            b':' if depth == 1 && key.is_none() => {
+                key = key_start.map(|start| start..last_end);
+                i += 1;
+                continue;
+            }
+            b',' | b'}' if depth == 1 => {
+                if let (Some(k), Some(v)) = (key.take(), value_start.take()) {
+                    entries.push(ConfigEntry {
+                        key: code[k].trim_matches(|c| c == '"' || c == '\'').to_string(),
+                        value: v..last_end,
+                    });
+                }
+                key_start = None;
+                if c == b'}' {
+                    return Some(entries);
+                }
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let start = i;
+        match c {
+            b'"' | b'\'' | b'`' => i = skip_string(b, i),
+            b'{' | b'[' | b'(' => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' | b']' | b')' => {
+                depth = depth.checked_sub(1)?;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+        if key.is_some() {
+            value_start.get_or_insert(start);
+        } else {
+            key_start.get_or_insert(start);
+        }
+        last_end = i;
+    }
+
+    None
+}
+
+fn find_config_open(code: &str) -> Option<usize> {
+    let b = code.as_bytes();
+    let mut search = 0;
+    while let Some(pos) = code[search..].find("CONFIG") {
+        let at = search + pos;
+        search = at + "CONFIG".len();
+        if at > 0 && (b[at - 1].is_ascii_alphanumeric() || b[at - 1] == b'_') {
+            continue;
+        }
+        let rest = code[search..].trim_start();
+        if let Some(rest) = rest.strip_prefix('=') {
+            if rest.starts_with('=') {
+                continue;
+            }
+            let rest = rest.trim_start();
+            if rest.starts_with('{') {
+                return Some(code.len() - rest.len());
+            }
+        }
+    }
+    None
+}
+
+fn skip_string(b: &[u8], start: usize) -> usize {
+    let quote = b[start];
+    let mut i = start + 1;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    b.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Top-level CONFIG keys with the source text of their values.
+    fn entries(code: &str) -> Vec<(String, &str)> {
+        config_section(code)
+            .expect("CONFIG section")
+            .into_iter()
+            .map(|e| (e.key, &code[e.value]))
+            .collect()
+    }
+
+    fn pairs<'a>(expected: &[(&str, &'a str)]) -> Vec<(String, &'a str)> {
+        expected.iter().map(|&(k, v)| (k.to_string(), v)).collect()
+    }
+
+    #[test]
+    fn nested_objects_and_arrays() {
+        let code = "let CONFIG = { a: { b: [1, 2], c: {} }, d: [ { e: 1 } ], f: g(1, 2) };";
+        assert_eq!(
+            entries(code),
+            pairs(&[
+                ("a", "{ b: [1, 2], c: {} }"),
+                ("d", "[ { e: 1 } ]"),
+                ("f", "g(1, 2)")
+            ])
+        );
+    }
+
+    #[test]
+    fn strings_with_delimiters() {
+        let code = r#"CONFIG = { url: "http://x/?a=1,b=}", 'quoted': 'it\'s, }', t: `a // b` }"#;
+        assert_eq!(
+            entries(code),
+            pairs(&[
+                ("url", r#""http://x/?a=1,b=}""#),
+                ("quoted", r"'it\'s, }'"),
+                ("t", "`a // b`")
+            ])
+        );
+    }
+
+    #[test]
+    fn comments_are_skipped() {
+        let code = "CONFIG = {\n  // the host, }\n  host: \"a\", // trailing\n  /* port: 1, } */ port: 80\n};";
+        assert_eq!(entries(code), pairs(&[("host", "\"a\""), ("port", "80")]));
+    }
+
+    #[test]
+    fn only_a_config_assignment_matches() {
+        assert!(config_section("if (CONFIG == { a: 1 }) {}").is_none());
+        assert!(config_section("let MY_CONFIG = { a: 1 };").is_none());
+        assert!(config_section("let CONFIG2 = { a: 1 };").is_none());
+        let code = "let MY_CONFIG = { a: 1 };\nlet CONFIG = { b: 2 };";
+        assert_eq!(entries(code), pairs(&[("b", "2")]));
+    }
+
+    #[test]
+    fn unbalanced_input_is_rejected() {
+        assert!(config_section("CONFIG = { a: f(1)) }").is_none());
+        assert!(config_section("CONFIG = { a: [1, 2 }").is_none());
+        assert!(config_section("CONFIG = { a: 1").is_none());
+    }
+
+    #[test]
+    fn overrides_replace_values_in_place() {
+        let code = "let CONFIG = { host: \"a\", port: 80, tags: [\"x\"] };\nprint(CONFIG);";
+        let overrides = [
+            ("tags".to_string(), json!(["y", "z"])),
+            ("host".to_string(), json!("a longer host")),
+            ("port".to_string(), json!(8080)),
+        ];
+        assert_eq!(
+            apply_overrides(code, &overrides).unwrap(),
+            "let CONFIG = { host: \"a longer host\", port: 8080, tags: [\"y\",\"z\"] };\nprint(CONFIG);"
+        );
+    }
+
+    #[test]
+    fn unknown_override_is_an_error() {
+        let overrides = [("nope".to_string(), json!(1))];
+        assert!(apply_overrides("CONFIG = { a: 1 }", &overrides).is_err());
+    }
+}
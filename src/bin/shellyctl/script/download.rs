@@ -1,4 +1,5 @@
 use crate::cli::DownloadScriptArgs;
+use crate::rpc::rpc_url;
 use anyhow::{anyhow, bail};
 use log::{debug, error, info, warn};
 use reqwest::Client;
@@ -11,7 +12,7 @@ pub async fn handle(args: DownloadScriptArgs) -> anyhow::Result<()> {
     let client = Client::new();
 
     // 1. Fetch all scripts
-    let list_url = format!("http://{}/rpc/Script.List", args.device);
+    let list_url = rpc_url(&args.device, "Script.List");
     debug!("Requesting Script.List from {}", list_url);
     let list_res = client.get(&list_url).send().await?;
     if !list_res.status().is_success() {
@@ -40,22 +41,8 @@ pub async fn handle(args: DownloadScriptArgs) -> anyhow::Result<()> {
     debug!("Resolved script '{}' to ID {}", args.name, id);
 
     // 3. Get the script code
-    let get_url = format!("http://{}/rpc/Script.GetCode", args.device);
     debug!("Requesting Script.GetCode for ID {}", id);
-    let get_res = client
-        .post(&get_url)
-        .json(&json!({ "id": id }))
-        .send()
-        .await?;
-    if !get_res.status().is_success() {
-        error!("Failed to fetch script code: {}", get_res.status());
-        bail!("Failed to fetch script code: {}", get_res.status());
-    }
-
-    let get_json: Value = get_res.json().await?;
-    let code = get_json["data"]
-        .as_str()
-        .ok_or_else(|| anyhow!("Missing or invalid 'data' field"))?;
+    let code = fetch_code(&client, &args.device, id as u64).await?;
 
     // 4. Output to stdout
     if args.stdout {
@@ -90,8 +77,37 @@ pub async fn handle(args: DownloadScriptArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Fetches the full code of script `id`, following Script.GetCode chunking.
+pub async fn fetch_code(client: &Client, device: &str, id: u64) -> anyhow::Result<String> {
+    let get_url = rpc_url(device, "Script.GetCode");
+    let mut code = String::new();
+    loop {
+        let get_res = client
+            .post(&get_url)
+            .json(&json!({ "id": id, "offset": code.len() }))
+            .send()
+            .await?;
+        if !get_res.status().is_success() {
+            error!("Failed to fetch script code: {}", get_res.status());
+            bail!("Failed to fetch script code: {}", get_res.status());
+        }
+
+        let get_json: Value = get_res.json().await?;
+        let data = get_json["data"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Missing or invalid 'data' field"))?;
+        code.push_str(data);
+
+        if data.is_empty() || get_json["left"].as_u64().unwrap_or(0) == 0 {
+            break;
+        }
+        debug!("{} bytes of script {} left", get_json["left"], id);
+    }
+    Ok(code)
+}
+
 // Helper to generate safe filename from script name
-fn generate_safe_filename(name: &str) -> String {
+pub fn generate_safe_filename(name: &str) -> String {
     let sanitized = name
         .chars()
         .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
@@ -0,0 +1,291 @@
+use crate::browse::{discover, Filter};
+use crate::cli::ScriptInventoryArgs;
+use crate::resolve::resolve_all;
+use crate::rpc::rpc_url;
+use crate::script::download::{fetch_code, generate_safe_filename};
+use crate::table::plain_table;
+use anyhow::{anyhow, bail, Result};
+use log::debug;
+use prettytable::{Cell, Row};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// Device list in the shellymon config format; unrelated keys are ignored.
+#[derive(Debug, Deserialize)]
+struct InventoryFile {
+    devices: Vec<InventoryDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InventoryDevice {
+    name: Option<String>,
+    address: String,
+}
+
+struct Target {
+    label: String,
+    address: String,
+}
+
+struct ScriptInfo {
+    device: String,
+    id: u64,
+    hash: String,
+    running: bool,
+    mem_used: Option<u64>,
+}
+
+pub async fn handle(args: ScriptInventoryArgs) -> Result<()> {
+    let targets = collect_targets(&args).await?;
+    if targets.is_empty() {
+        println!("No devices found.");
+        return Ok(());
+    }
+
+    let client = Client::new();
+    let mut tasks = JoinSet::new();
+    for target in targets {
+        let client = client.clone();
+        tasks.spawn(async move {
+            let res = fetch_scripts(&client, &target).await;
+            (target, res)
+        });
+    }
+
+    // script name -> code hash -> copies
+    let mut inventory = BTreeMap::<String, BTreeMap<String, Vec<ScriptInfo>>>::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (target, res) = joined?;
+        match res {
+            Ok(scripts) => {
+                for (name, info) in scripts {
+                    inventory
+                        .entry(name)
+                        .or_default()
+                        .entry(info.hash.clone())
+                        .or_default()
+                        .push(info);
+                }
+            }
+            Err(e) => eprintln!("❌ {}: {}", target.label, e),
+        }
+    }
+
+    if inventory.is_empty() {
+        println!("No scripts found.");
+        return Ok(());
+    }
+
+    for copies in inventory.values_mut().flat_map(|v| v.values_mut()) {
+        copies.sort_by(|a, b| a.device.cmp(&b.device));
+    }
+
+    let reference = args.reference.as_deref().map(Path::new);
+
+    let mut table = plain_table();
+
+    let mut header = vec![
+        Cell::new("Script").style_spec("Fc"),
+        Cell::new("Hash").style_spec("Fc"),
+        Cell::new("Device").style_spec("Fc"),
+        Cell::new("ID").style_spec("Fc"),
+        Cell::new("Running").style_spec("Fc"),
+        Cell::new("Memory").style_spec("Fc"),
+    ];
+    if reference.is_some() {
+        header.push(Cell::new("Status").style_spec("Fc"));
+    }
+    table.add_row(Row::new(header));
+
+    let mut drifted = Vec::new();
+    let mut outdated = 0;
+
+    for (name, variants) in &inventory {
+        if variants.len() > 1 {
+            drifted.push((name, variants.len()));
+        }
+
+        let reference_hash = match reference {
+            Some(dir) => reference_hash(dir, name)?,
+            None => None,
+        };
+
+        for (v, (hash, copies)) in variants.iter().enumerate() {
+            for (i, copy) in copies.iter().enumerate() {
+                let (name_cell, hash_cell) = if i == 0 {
+                    let name_cell = if v == 0 { name.as_str() } else { "" };
+                    (name_cell, &hash[..12])
+                } else {
+                    ("", "")
+                };
+
+                let mut row = vec![
+                    Cell::new(name_cell).style_spec("Fg"),
+                    Cell::new(hash_cell).style_spec("Fy"),
+                    Cell::new(&copy.device).style_spec("Fw"),
+                    Cell::new(&copy.id.to_string()).style_spec("Fw"),
+                    Cell::new(&copy.running.to_string()).style_spec("Fy"),
+                    Cell::new(
+                        &copy
+                            .mem_used
+                            .map(|m| m.to_string())
+                            .unwrap_or_else(|| "-".to_string()),
+                    )
+                    .style_spec("Fw"),
+                ];
+                if reference.is_some() {
+                    row.push(match &reference_hash {
+                        Some(r) if r == hash => Cell::new("up to date").style_spec("Fg"),
+                        Some(_) => {
+                            outdated += 1;
+                            Cell::new("outdated").style_spec("Fr")
+                        }
+                        None => Cell::new("no reference").style_spec("Fw"),
+                    });
+                }
+                table.add_row(Row::new(row));
+            }
+        }
+    }
+
+    table.printstd();
+
+    if !drifted.is_empty() || outdated > 0 {
+        println!();
+    }
+    for (name, count) in drifted {
+        println!(
+            "⚠️  '{}' has {} different versions across devices",
+            name, count
+        );
+    }
+    if outdated > 0 {
+        println!("⚠️  {} outdated script copies", outdated);
+    }
+
+    Ok(())
+}
+
+async fn collect_targets(args: &ScriptInventoryArgs) -> Result<Vec<Target>> {
+    let mut targets: Vec<Target> = args
+        .devices
+        .iter()
+        .map(|d| Target {
+            label: d.clone(),
+            address: d.clone(),
+        })
+        .collect();
+
+    if let Some(path) = &args.inventory {
+        let data =
+            fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+        let inventory: InventoryFile =
+            toml::from_str(&data).map_err(|e| anyhow!("Invalid inventory {}: {}", path, e))?;
+        let mut listed: Vec<Target> = inventory
+            .devices
+            .into_iter()
+            .map(|d| Target {
+                label: d.name.unwrap_or_else(|| d.address.clone()),
+                address: d.address,
+            })
+            .collect();
+        resolve_all(listed.iter_mut().map(|t| &mut t.address).collect()).await;
+        targets.extend(listed);
+    }
+
+    if targets.is_empty() {
+        println!("🔍 Scanning for Shelly devices on the network (5s)...\n");
+        let devices = discover(Duration::from_secs(5), None, &Filter::default(), |_, _| {
+            Ok(())
+        })
+        .await?;
+        // Gen1 devices have no scripts
+        targets.extend(
+            devices
+                .into_values()
+                .filter(|d| d.gen >= 2)
+                .map(|d| Target {
+                    label: d.hostname,
+                    address: d.ip,
+                }),
+        );
+    }
+
+    Ok(targets)
+}
+
+async fn fetch_scripts(client: &Client, target: &Target) -> Result<Vec<(String, ScriptInfo)>> {
+    let list_url = rpc_url(&target.address, "Script.List");
+    let list_res = client.get(&list_url).send().await?;
+    if !list_res.status().is_success() {
+        bail!("Failed to fetch script list: {}", list_res.status());
+    }
+
+    let list_json: Value = list_res.json().await?;
+    let scripts = list_json["scripts"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Invalid response: missing 'scripts' array"))?;
+
+    let mut result = Vec::new();
+    for script in scripts {
+        let id = script["id"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("Missing or invalid script ID"))?;
+        let name = script["name"].as_str().unwrap_or("<unknown>").to_string();
+        let running = script["running"].as_bool().unwrap_or(false);
+
+        let code = fetch_code(client, &target.address, id).await?;
+
+        let mem_used = if running {
+            let status_url = rpc_url(&target.address, "Script.GetStatus");
+            let status: Value = client
+                .post(&status_url)
+                .json(&json!({ "id": id }))
+                .send()
+                .await?
+                .json()
+                .await?;
+            status["mem_used"].as_u64()
+        } else {
+            None
+        };
+
+        debug!("{}: script '{}' (ID {})", target.label, name, id);
+        result.push((
+            name,
+            ScriptInfo {
+                device: target.label.clone(),
+                id,
+                hash: sha256_hex(code.as_bytes()),
+                running,
+                mem_used,
+            },
+        ));
+    }
+
+    Ok(result)
+}
+
+fn reference_hash(dir: &Path, name: &str) -> Result<Option<String>> {
+    let path = dir.join(generate_safe_filename(name));
+    if !path.exists() {
+        debug!("No reference for '{}' at {}", name, path.display());
+        return Ok(None);
+    }
+    let code = fs::read(&path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(Some(sha256_hex(&code)))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
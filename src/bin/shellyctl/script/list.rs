@@ -1,14 +1,13 @@
 use crate::cli::ListScriptsArgs;
-use prettytable::{
-    format::{FormatBuilder, LinePosition, LineSeparator},
-    Cell, Row, Table,
-};
+use crate::rpc::rpc_url;
+use crate::table::plain_table;
+use prettytable::{Cell, Row};
 use reqwest::Client;
 use serde_json::Value;
 
 pub async fn handle(args: ListScriptsArgs) -> anyhow::Result<()> {
     let client = Client::new();
-    let url = format!("http://{}/rpc/Script.List", args.device);
+    let url = rpc_url(&args.device, "Script.List");
     let res = client.get(&url).send().await?;
 
     if !res.status().is_success() {
@@ -25,25 +24,7 @@ pub async fn handle(args: ListScriptsArgs) -> anyhow::Result<()> {
         return Ok(());
     }
 
-    let mut table = Table::new();
-    let format = FormatBuilder::new()
-        .column_separator(' ')
-        .borders('\0')
-        .separator(
-            LinePosition::Top,
-            LineSeparator::new('\0', '\0', '\0', '\0'),
-        )
-        .separator(
-            LinePosition::Title,
-            LineSeparator::new('\0', '\0', '\0', '\0'),
-        )
-        .separator(
-            LinePosition::Bottom,
-            LineSeparator::new('\0', '\0', '\0', '\0'),
-        )
-        .padding(0, 0)
-        .build();
-    table.set_format(format);
+    let mut table = plain_table();
 
     // Header row with style_spec
     table.add_row(Row::new(vec![
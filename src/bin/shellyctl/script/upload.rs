@@ -1,4 +1,5 @@
 use crate::cli::UploadScriptArgs;
+use crate::rpc::rpc_url;
 use log::{debug, error, info, warn};
 use reqwest::Client;
 use serde_json::json;
@@ -8,8 +9,31 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
     let code = std::fs::read_to_string(&args.file)?;
     debug!("Read script from file: {}", args.file);
 
+    upload(
+        &client,
+        &args.device,
+        &args.name,
+        &code,
+        args.force,
+        args.enable,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Creates or overwrites the script `name` on `device` with `code`.
+///
+/// Returns the script ID, or `None` if the script is running and `force` is not set.
+pub async fn upload(
+    client: &Client,
+    device: &str,
+    name: &str,
+    code: &str,
+    force: bool,
+    enable: bool,
+) -> anyhow::Result<Option<u8>> {
     // 1. Call Script.List to find the script by name
-    let list_url = format!("http://{}/rpc/Script.List", args.device);
+    let list_url = rpc_url(device, "Script.List");
     let list_res = client.get(&list_url).send().await?;
     let list_json: serde_json::Value = list_res.json().await?;
     debug!("Script.List response: {:?}", list_json);
@@ -20,9 +44,7 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
         .ok_or_else(|| anyhow::anyhow!("Missing or invalid 'scripts' array in Script.List"))?;
 
     // 2. Search for the script by name
-    let matching_script = scripts
-        .iter()
-        .find(|s| s.get("name") == Some(&json!(args.name)));
+    let matching_script = scripts.iter().find(|s| s.get("name") == Some(&json!(name)));
 
     let script_id: u8;
 
@@ -31,14 +53,11 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("Invalid script ID in Script.List response"))?
             as u8;
-        debug!("Found script '{}' with ID {}", args.name, script_id);
+        debug!("Found script '{}' with ID {}", name, script_id);
 
-        if existing["running"].as_bool().unwrap_or(false) && args.force {
-            info!(
-                "Stopping running script '{}' (ID {})...",
-                args.name, script_id
-            );
-            let stop_url = format!("http://{}/rpc/Script.Stop", args.device);
+        if existing["running"].as_bool().unwrap_or(false) && force {
+            info!("Stopping running script '{}' (ID {})...", name, script_id);
+            let stop_url = rpc_url(device, "Script.Stop");
             client
                 .post(&stop_url)
                 .json(&json!({ "id": script_id }))
@@ -48,17 +67,17 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
         } else if existing["running"].as_bool().unwrap_or(false) {
             warn!(
                 "Script '{}' is running. Use --force to stop and overwrite.",
-                args.name
+                name
             );
-            return Ok(());
+            return Ok(None);
         }
     } else {
         // 3. Script not found → Create it
-        info!("Script '{}' not found. Creating it...", args.name);
-        let create_url = format!("http://{}/rpc/Script.Create", args.device);
+        info!("Script '{}' not found. Creating it...", name);
+        let create_url = rpc_url(device, "Script.Create");
         let create_res = client
             .post(&create_url)
-            .json(&json!({ "name": args.name }))
+            .json(&json!({ "name": name }))
             .send()
             .await?;
 
@@ -69,11 +88,11 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
             .as_u64()
             .ok_or_else(|| anyhow::anyhow!("Missing 'id' in Script.Create response"))?
             as u8;
-        info!("Created script '{}' with ID {}", args.name, script_id);
+        info!("Created script '{}' with ID {}", name, script_id);
     }
 
     // 4. Upload code
-    let put_url = format!("http://{}/rpc/Script.PutCode", args.device);
+    let put_url = rpc_url(device, "Script.PutCode");
     let payload = json!({ "id": script_id, "code": code });
     debug!(
         "Uploading code to script ID {}: {}",
@@ -90,11 +109,11 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
         anyhow::bail!("Script.PutCode failed: {}", status);
     }
 
-    info!("Uploaded code to script '{}'", args.name);
+    info!("Uploaded code to script '{}'", name);
 
     // 5. Optionally enable script
-    if args.enable {
-        let status_url = format!("http://{}/rpc/Script.GetStatus", args.device);
+    if enable {
+        let status_url = rpc_url(device, "Script.GetStatus");
         let status_res = client
             .post(&status_url)
             .json(&json!({ "id": script_id }))
@@ -103,10 +122,10 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
         let status_json: serde_json::Value = status_res.json().await?;
 
         if status_json["running"].as_bool().unwrap_or(false) {
-            info!("Script '{}' is already enabled", args.name);
+            info!("Script '{}' is already enabled", name);
         } else {
-            info!("Enabling script '{}'...", args.name);
-            let enable_url = format!("http://{}/rpc/Script.Enable", args.device);
+            info!("Enabling script '{}'...", name);
+            let enable_url = rpc_url(device, "Script.Enable");
             client
                 .post(&enable_url)
                 .json(&json!({ "id": script_id }))
@@ -118,5 +137,5 @@ pub async fn handle(args: UploadScriptArgs) -> anyhow::Result<()> {
         debug!("Skipping script enable (--enable not passed)");
     }
 
-    Ok(())
+    Ok(Some(script_id))
 }
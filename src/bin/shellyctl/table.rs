@@ -0,0 +1,30 @@
+//! The borderless table layout shared by every listing command.
+
+use prettytable::{
+    format::{FormatBuilder, LinePosition, LineSeparator},
+    Table,
+};
+
+/// An empty table without borders, separators or padding.
+pub fn plain_table() -> Table {
+    let mut table = Table::new();
+    let format = FormatBuilder::new()
+        .column_separator(' ')
+        .borders('\0')
+        .separator(
+            LinePosition::Top,
+            LineSeparator::new('\0', '\0', '\0', '\0'),
+        )
+        .separator(
+            LinePosition::Title,
+            LineSeparator::new('\0', '\0', '\0', '\0'),
+        )
+        .separator(
+            LinePosition::Bottom,
+            LineSeparator::new('\0', '\0', '\0', '\0'),
+        )
+        .padding(0, 0)
+        .build();
+    table.set_format(format);
+    table
+}
@@ -10,6 +10,19 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+// Shared with shellyctl; only lookups are needed here
+#[path = "../shellyctl/path.rs"]
+#[allow(dead_code)]
+mod path;
+
+// Shared with shellyctl for name resolution and device URLs
+#[path = "../shellyctl/resolve.rs"]
+#[allow(dead_code)]
+mod resolve;
+#[path = "../shellyctl/rpc.rs"]
+#[allow(dead_code)]
+mod rpc;
+
 #[derive(Parser, Debug)]
 #[command(name = "shellymon")]
 struct Cli {
@@ -73,12 +86,13 @@ fn load_config(path: &str) -> Config {
     toml::from_str(&config_data).expect("Failed to parse config")
 }
 
-fn extract_json_value(json: &serde_json::Value, path: &str) -> Option<f64> {
-    let mut current = json;
-    for part in path.split('.') {
-        current = current.get(part)?;
-    }
-    current.as_f64()
+fn extract_json_value(json: &serde_json::Value, path: &str) -> Result<f64, String> {
+    let value = path::Path::parse(path)
+        .and_then(|p| p.get(json))
+        .map_err(|e| e.to_string())?;
+    value
+        .as_f64()
+        .ok_or_else(|| format!("'{}' is not a number: {}", path, value))
 }
 
 fn run_monitoring_loop(devices: &[DeviceConfig], running: Arc<AtomicBool>) {
@@ -99,7 +113,7 @@ fn run_monitoring_loop(devices: &[DeviceConfig], running: Arc<AtomicBool>) {
                 .unwrap_or_else(|| now - Duration::from_secs(device.interval));
 
             if now.duration_since(last).as_secs() >= device.interval {
-                let url = format!("http://{}/rpc/Shelly.GetStatus", device.address);
+                let url = rpc::rpc_url(&device.address, "Shelly.GetStatus");
                 info!("Polling {} at {}", device.name, url);
 
                 match client.get(&url).send() {
@@ -122,15 +136,16 @@ fn run_monitoring_loop(devices: &[DeviceConfig], running: Arc<AtomicBool>) {
                                 for (json_path, column_name) in &device.fields {
                                     ensure_column(&conn, &device.table, column_name);
 
-                                    if let Some(val) = extract_json_value(&json, json_path) {
-                                        sql.push_str(&format!(", {}", column_name));
-                                        placeholders.push_str(", ?");
-                                        raw_values.push(val);
-                                    } else {
-                                        warn!(
-                                            "Missing or invalid value for '{}' on device '{}'",
-                                            json_path, device.name
-                                        );
+                                    match extract_json_value(&json, json_path) {
+                                        Ok(val) => {
+                                            sql.push_str(&format!(", {}", column_name));
+                                            placeholders.push_str(", ?");
+                                            raw_values.push(val);
+                                        }
+                                        Err(e) => warn!(
+                                            "Missing or invalid value on device '{}': {}",
+                                            device.name, e
+                                        ),
                                     }
                                 }
 
@@ -179,7 +194,13 @@ fn main() -> Result<()> {
         env_logger::init();
     }
 
-    let config = load_config(&cli.config);
+    let mut config = load_config(&cli.config);
+
+    // Names are resolved once up front instead of on every poll
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start runtime");
+    runtime.block_on(resolve::resolve_all(
+        config.devices.iter_mut().map(|d| &mut d.address).collect(),
+    ));
 
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();